@@ -13,7 +13,7 @@ mod score;
 
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::ValueEnum;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(ValueEnum, Clone, Copy, Debug, strum::Display, strum::EnumIter)]
 #[clap(rename_all = "lowercase")]
@@ -85,23 +85,39 @@ impl App {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum MountRole {
     WorkingDir,
     Scratch,
+    /// Read-only inputs from this host path, e.g. a shared structure library
+    Input(Utf8PathBuf),
+    /// Data from this host path the app may write to, e.g. a persistent database
+    Data(Utf8PathBuf),
 }
 
 // ContainerConfig or ContainerExecConfig
 pub struct ContainerConfig {
     //pub image: Image,
     pub args: Vec<String>,
-    pub mounts: HashMap<MountRole, String>,
+    /// Mounts with their container paths in the order they were added, a mount replaces an
+    /// earlier one at the same container path
+    pub mounts: Vec<(MountRole, String)>,
     pub entrypoint: Option<String>,
+    /// Environment variables for the app, user supplied ones take precedence
+    pub env: BTreeMap<String, String>,
 }
 
 pub struct NativeRunSpec {
     //pub pixi: Cow<'static, str>,
+    /// Program and its arguments, passed as is without a shell. `$VAR` and `${VAR}` references
+    /// to `PIXI_PROJECT_ROOT`, `CONDA_PREFIX` and the recipe's `[activation.env]` variables are
+    /// expanded by the native engine
     pub args: Vec<String>,
+    /// Directory to run in, relative to the Pixi environment root, `None` for the working
+    /// directory. Variable references are expanded as in `args`
+    pub cwd: Option<Utf8PathBuf>,
+    /// Environment variables for the app, user supplied ones take precedence
+    pub env: BTreeMap<String, String>,
 }
 
 pub trait AppSpec {
@@ -119,6 +135,26 @@ pub trait AppSpec {
         None
     }
 
+    /// Project homepage or documentation, shown by `rc info`.
+    fn homepage(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Positional arguments the app expects first, e.g. `<SCRIPT>`, shown by `rc info`.
+    fn required_args(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Arguments injected into container runs unless overridden, shown by `rc info`.
+    fn default_args(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether the app needs a GPU to run at a useful speed, runs get every GPU by default.
+    fn wants_gpu(&self) -> bool {
+        false
+    }
+
     fn container_spec(&self, args: Vec<String>) -> ContainerConfig;
     // {
     //     ContainerRunSpec::new(self.container_image(), args).working_dir("/w")
@@ -135,8 +171,9 @@ impl ContainerConfig {
         Self {
             //image: Image(image.into()),
             args,
-            mounts: HashMap::new(),
+            mounts: Vec::new(),
             entrypoint: None,
+            env: BTreeMap::new(),
         }
     }
     pub fn with_prefixed_args<I1, I2, S1, S2>(
@@ -159,28 +196,56 @@ impl ContainerConfig {
         Self {
             // image: Image(image.into()),
             args: full_args,
-            mounts: HashMap::new(),
+            mounts: Vec::new(),
             entrypoint: None,
+            env: BTreeMap::new(),
         }
     }
 
-    pub fn scratch(mut self, p: impl Into<String>) -> Self {
-        self.mounts.insert(MountRole::Scratch, p.into());
+    fn mount(mut self, role: MountRole, p: String) -> Self {
+        let single = matches!(role, MountRole::WorkingDir | MountRole::Scratch);
+        self.mounts
+            .retain(|(r, c)| *c != p && !(single && *r == role));
+        self.mounts.push((role, p));
         self
     }
-    pub fn working_dir(mut self, p: impl Into<String>) -> Self {
-        self.mounts.insert(MountRole::WorkingDir, p.into());
-        self
+    pub fn scratch(self, p: impl Into<String>) -> Self {
+        self.mount(MountRole::Scratch, p.into())
+    }
+    pub fn working_dir(self, p: impl Into<String>) -> Self {
+        self.mount(MountRole::WorkingDir, p.into())
     }
     pub fn entrypoint(mut self, p: impl Into<String>) -> Self {
         self.entrypoint = Some(p.into());
         self
     }
+    pub fn input(self, host: impl Into<Utf8PathBuf>, p: impl Into<String>) -> Self {
+        self.mount(MountRole::Input(host.into()), p.into())
+    }
+    pub fn data(self, host: impl Into<Utf8PathBuf>, p: impl Into<String>) -> Self {
+        self.mount(MountRole::Data(host.into()), p.into())
+    }
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
+    }
 }
 
 impl NativeRunSpec {
     pub fn new(args: Vec<String>) -> Self {
-        Self { args }
+        Self {
+            args,
+            cwd: None,
+            env: BTreeMap::new(),
+        }
+    }
+    pub fn cwd(mut self, p: impl Into<Utf8PathBuf>) -> Self {
+        self.cwd = Some(p.into());
+        self
+    }
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.insert(key.into(), value.into());
+        self
     }
     // pub fn pixi(mut self, p: impl Into<Cow<'static, str>>) -> Self {
     //     self.pixi = p.into();
@@ -2,6 +2,8 @@ use camino::Utf8Path;
 
 use crate::app::{AppSpec, ContainerConfig, NativeRunSpec};
 
+const DEFAULT_ARGS: &[&str] = &["colabfold_batch"];
+
 pub struct Colabfold;
 pub static COLABFOLD: Colabfold = Colabfold;
 
@@ -14,8 +16,25 @@ impl AppSpec for Colabfold {
         None
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/sokrypton/ColabFold")
+    }
+
+    fn required_args(&self) -> &'static [&'static str] {
+        &["<INPUT>", "<RESULTS_DIR>"]
+    }
+
+    fn wants_gpu(&self) -> bool {
+        true
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
-        ContainerConfig::with_prefixed_args(["colabfold_batch"], app_args).working_dir("/w")
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
+            .working_dir("/w")
     }
 
     fn native_spec(&self, mut _app_args: Vec<String>, _working_dir: &Utf8Path) -> NativeRunSpec {
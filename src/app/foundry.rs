@@ -24,26 +24,20 @@ pub static FOUNDRY: Foundry = Foundry;
 /// provided their own checkpoint.
 fn with_default_checkpoints(mut app_args: Vec<String>, weights_path: &str) -> Vec<String> {
     match app_args[0].as_str() {
-        "mpnn" => {
-            if !app_args.iter().any(|arg| arg == "--checkpoint_path") {
-                app_args.extend([
-                    "--checkpoint_path".into(),
-                    format!("{weights_path}/ligandmpnn_v_32_010_25.pt"),
-                ]);
-            }
+        "mpnn" if !app_args.iter().any(|arg| arg == "--checkpoint_path") => {
+            app_args.extend([
+                "--checkpoint_path".into(),
+                format!("{weights_path}/ligandmpnn_v_32_010_25.pt"),
+            ]);
         }
-        "rf3" => {
-            if !app_args.iter().any(|arg| arg.starts_with("ckpt_path=")) {
-                app_args.insert(
-                    app_args.len().min(2),
-                    format!("ckpt_path={weights_path}/rf3_foundry_01_24_latest_remapped.ckpt"),
-                );
-            }
+        "rf3" if !app_args.iter().any(|arg| arg.starts_with("ckpt_path=")) => {
+            app_args.insert(
+                app_args.len().min(2),
+                format!("ckpt_path={weights_path}/rf3_foundry_01_24_latest_remapped.ckpt"),
+            );
         }
-        "rfd3" => {
-            if !app_args.iter().any(|arg| arg.starts_with("ckpt_path=")) {
-                app_args.insert(1, format!("ckpt_path={weights_path}/rfd3_latest.ckpt"));
-            }
+        "rfd3" if !app_args.iter().any(|arg| arg.starts_with("ckpt_path=")) => {
+            app_args.insert(1, format!("ckpt_path={weights_path}/rfd3_latest.ckpt"));
         }
         _ => {}
     }
@@ -101,6 +95,26 @@ impl AppSpec for Foundry {
         Some(include_asset!("pixi/foundry.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/RosettaCommons/foundry")
+    }
+
+    fn required_args(&self) -> &'static [&'static str] {
+        &["<mpnn|rf3|rfd3>"]
+    }
+
+    fn wants_gpu(&self) -> bool {
+        true
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        &[
+            "mpnn: --checkpoint_path /weights/ligandmpnn_v_32_010_25.pt",
+            "rf3: ckpt_path=/weights/rf3_foundry_01_24_latest_remapped.ckpt",
+            "rfd3: ckpt_path=/weights/rfd3_latest.ckpt",
+        ]
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
         assert!(
             !(app_args.is_empty() || app_args[0].starts_with("-")),
@@ -116,11 +130,6 @@ impl AppSpec for Foundry {
             "Foundry arguments must include a protocol name as first argument"
         );
 
-        let app_args = app_args
-            .into_iter()
-            .map(|a| shell_escape::escape(a.into()).into())
-            .collect::<Vec<_>>();
-
         let app_args = with_default_checkpoints(app_args, "$FOUNDRY_CHECKPOINTS");
 
         let app_args = match app_args[0].as_str() {
@@ -129,11 +138,6 @@ impl AppSpec for Foundry {
             _ => app_args,
         };
 
-        // for arg in &mut app_args {
-        //     *arg = shell_escape::escape(arg.as_str().into()).to_string();
-        // }
-        // app_args.insert(0, format!("cd {} && ", working_dir.to_string_lossy()));
-
         NativeRunSpec::new(app_args)
     }
 }
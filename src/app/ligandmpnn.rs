@@ -5,6 +5,12 @@ use crate::{
     util::include_asset,
 };
 
+const DEFAULT_ARGS: &[&str] = &[
+    "--out_folder=/w",
+    "--checkpoint_protein_mpnn",
+    "/app/ligandmpnn/model_params/proteinmpnn_v_48_020.pt",
+];
+
 pub struct Ligandmpnn;
 pub static LIGANDMPNN: Ligandmpnn = Ligandmpnn;
 
@@ -44,16 +50,17 @@ impl AppSpec for Ligandmpnn {
         Some(include_asset!("pixi/ligandmpnn.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/dauparas/LigandMPNN")
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
-        ContainerConfig::with_prefixed_args(
-            [
-                "--out_folder=/w",
-                "--checkpoint_protein_mpnn",
-                "/app/ligandmpnn/model_params/proteinmpnn_v_48_020.pt",
-            ],
-            app_args,
-        )
-        .working_dir("/w")
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
+            .working_dir("/w")
 
         // app_args.splice(
         //     0..0,
@@ -67,22 +74,19 @@ impl AppSpec for Ligandmpnn {
     }
 
     fn native_spec(&self, app_args: Vec<String>, working_dir: &Utf8Path) -> NativeRunSpec {
-        let app_args = app_args
-            .into_iter()
-            .map(|arg| shell_escape::escape(arg.into()).into())
-            .collect::<Vec<_>>();
-
         let mut app_args = map_input_and_output_options(app_args, working_dir);
 
         app_args.splice(
             0..0,
             [
+                "python".into(),
+                "run.py".into(),
                 "--checkpoint_protein_mpnn".into(),
                 "$LIGANDMPNN_WEIGHTS/proteinmpnn_v_48_020.pt".into(),
             ],
         );
 
-        NativeRunSpec::new(app_args)
+        NativeRunSpec::new(app_args).cwd("ligandmpnn-repo-clone")
     }
 }
 
@@ -5,6 +5,8 @@ use crate::{
     util::include_asset,
 };
 
+const DEFAULT_ARGS: &[&str] = &["run_openfold"];
+
 pub struct Openfold3;
 pub static OPENFOLD3: Openfold3 = Openfold3;
 
@@ -17,8 +19,21 @@ impl AppSpec for Openfold3 {
         Some(include_asset!("pixi/openfold3.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://openfold.io")
+    }
+
+    fn wants_gpu(&self) -> bool {
+        true
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
-        ContainerConfig::with_prefixed_args(["run_openfold"], app_args).working_dir("/w")
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
+            .working_dir("/w")
     }
 
     fn native_spec(&self, app_args: Vec<String>, working_dir: &Utf8Path) -> NativeRunSpec {
@@ -28,9 +43,13 @@ impl AppSpec for Openfold3 {
         );
         let json_file = make_absolute(working_dir, &app_args[0]);
 
-        let app_args = vec![format!(
-            "--output-dir={working_dir} --query_json={json_file}"
-        )];
+        let app_args = vec![
+            "run_openfold".into(),
+            "predict".into(),
+            "--runner-yaml=$OPENFOLD_CPU_RUNNER_YAML".into(),
+            format!("--output-dir={working_dir}"),
+            format!("--query_json={json_file}"),
+        ];
 
         NativeRunSpec::new(app_args)
     }
@@ -2,6 +2,8 @@ use camino::Utf8Path;
 
 use crate::app::{AppSpec, ContainerConfig, NativeRunSpec};
 
+const DEFAULT_ARGS: &[&str] = &["PatchMAN_protocol_dask.py", "-w", "/w"];
+
 pub struct Patchman;
 pub static PATCHMAN: Patchman = Patchman;
 
@@ -14,6 +16,18 @@ impl AppSpec for Patchman {
         None
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/Furman-Lab/PatchMAN")
+    }
+
+    fn required_args(&self) -> &'static [&'static str] {
+        &["<PDB>", "<SEQUENCE>"]
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
         assert!(
             (app_args.len() >= 2),
@@ -24,7 +38,7 @@ impl AppSpec for Patchman {
         //     *arg = make_absolute("/w".into(), arg).into();
         // }
 
-        ContainerConfig::with_prefixed_args(["PatchMAN_protocol_dask.py", "-w", "/w"], app_args)
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
             .working_dir("/w")
     }
 
@@ -5,6 +5,8 @@ use crate::{
     util::include_asset,
 };
 
+const DEFAULT_ARGS: &[&str] = &["--out_folder=/w"];
+
 pub struct Proteinmpnn;
 pub static PROTEINMPNN: Proteinmpnn = Proteinmpnn;
 
@@ -46,8 +48,17 @@ impl AppSpec for Proteinmpnn {
         Some(include_asset!("pixi/proteinmpnn.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/dauparas/ProteinMPNN")
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
-        ContainerConfig::with_prefixed_args(["--out_folder=/w"], app_args).working_dir("/w")
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
+            .working_dir("/w")
     }
 
     fn native_spec(&self, mut app_args: Vec<String>, working_dir: &Utf8Path) -> NativeRunSpec {
@@ -55,12 +66,7 @@ impl AppSpec for Proteinmpnn {
 
         let app_args = map_input_and_output_options(app_args, working_dir);
 
-        let app_args = app_args
-            .into_iter()
-            .map(|arg| shell_escape::escape(arg.into()).into())
-            .collect::<Vec<_>>();
-
-        NativeRunSpec::new(app_args)
+        NativeRunSpec::new(app_args).cwd("proteinmpnn-repo-clone")
     }
 }
 
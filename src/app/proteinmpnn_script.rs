@@ -53,6 +53,18 @@ impl AppSpec for ProteinmpnnScript {
         Some(include_asset!("pixi/proteinmpnn.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/dauparas/ProteinMPNN/tree/main/helper_scripts")
+    }
+
+    fn required_args(&self) -> &'static [&'static str] {
+        &["<SCRIPT.py>"]
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        &["--input_path=/w", "--output_path=/w"]
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
         assert!(
             !(app_args.is_empty() || app_args[0].starts_with("-")),
@@ -93,12 +105,7 @@ impl AppSpec for ProteinmpnnScript {
             app_args
         };
 
-        let app_args = app_args
-            .into_iter()
-            .map(|arg| shell_escape::escape(arg.into()).into())
-            .collect::<Vec<_>>();
-
-        NativeRunSpec::new(app_args)
+        NativeRunSpec::new(app_args).cwd("proteinmpnn-repo-clone")
     }
 }
 
@@ -2,6 +2,8 @@ use camino::Utf8Path;
 
 use crate::app::{AppSpec, ContainerConfig, NativeRunSpec};
 
+const DEFAULT_ARGS: &[&str] = &["python"];
+
 pub struct Pyrosetta;
 pub static PYROSETTA: Pyrosetta = Pyrosetta;
 
@@ -10,8 +12,21 @@ impl AppSpec for Pyrosetta {
         "rosettacommons/rosetta:serial"
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://www.pyrosetta.org/")
+    }
+
+    fn required_args(&self) -> &'static [&'static str] {
+        &["<SCRIPT.py>"]
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
-        ContainerConfig::with_prefixed_args(["python"], app_args).working_dir("/w")
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
+            .working_dir("/w")
     }
     fn native_spec(&self, _app_args: Vec<String>, _working_dir: &Utf8Path) -> NativeRunSpec {
         unimplemented!()
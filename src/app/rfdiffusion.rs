@@ -5,6 +5,14 @@ use crate::{
     util::include_asset,
 };
 
+const DEFAULT_ARGS: &[&str] = &[
+    "inference.output_prefix=/w/",
+    "inference.model_directory_path=/app/RFdiffusion/models",
+];
+
+/// Show full Hydra tracebacks instead of the truncated default
+const HYDRA_FULL_ERROR: &str = "HYDRA_FULL_ERROR";
+
 pub struct Rfdiffusion;
 pub static RFDIFFUSION: Rfdiffusion = Rfdiffusion;
 
@@ -17,16 +25,23 @@ impl AppSpec for Rfdiffusion {
         Some(include_asset!("pixi/rfdiffusion.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://github.com/RosettaCommons/RFdiffusion")
+    }
+
+    fn wants_gpu(&self) -> bool {
+        true
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        DEFAULT_ARGS
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
-        ContainerConfig::with_prefixed_args(
-            [
-                "inference.output_prefix=/w/",
-                "inference.model_directory_path=/app/RFdiffusion/models",
-            ],
-            app_args,
-        )
-        .scratch("/app/RFdiffusion/schedules")
-        .working_dir("/w")
+        ContainerConfig::with_prefixed_args(DEFAULT_ARGS.iter().copied(), app_args)
+            .scratch("/app/RFdiffusion/schedules")
+            .working_dir("/w")
+            .env(HYDRA_FULL_ERROR, "1")
     }
 
     fn native_spec(&self, mut app_args: Vec<String>, working_dir: &Utf8Path) -> NativeRunSpec {
@@ -57,11 +72,10 @@ impl AppSpec for Rfdiffusion {
             app_args.push(format!("{OUTPUT_PREFIX}{}/", working_dir));
         }
 
-        let app_args = app_args
-            .into_iter()
-            .map(|arg| shell_escape::escape(arg.into()).into())
-            .collect::<Vec<_>>();
+        app_args.splice(0..0, ["python".into(), "scripts/run_inference.py".into()]);
 
         NativeRunSpec::new(app_args)
+            .cwd("rfdiffusion-repo-clone")
+            .env(HYDRA_FULL_ERROR, "1")
     }
 }
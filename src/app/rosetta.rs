@@ -17,12 +17,19 @@ impl AppSpec for Rosetta {
         Some(include_asset!("pixi/rosetta.toml"))
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some("https://docs.rosettacommons.org/docs/latest/Home")
+    }
+
+    fn required_args(&self) -> &'static [&'static str] {
+        &["<APPLICATION>"]
+    }
+
     fn container_spec(&self, app_args: Vec<String>) -> ContainerConfig {
         ContainerConfig::new(app_args).working_dir("/w")
     }
 
-    fn native_spec(&self, mut app_args: Vec<String>, working_dir: &Utf8Path) -> NativeRunSpec {
-        app_args.insert(0, format!("cd {working_dir} &&"));
+    fn native_spec(&self, app_args: Vec<String>, _working_dir: &Utf8Path) -> NativeRunSpec {
         NativeRunSpec::new(app_args)
     }
 }
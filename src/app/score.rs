@@ -19,6 +19,16 @@ impl AppSpec for Score {
         rosetta::ROSETTA.pixi_recipe()
     }
 
+    fn homepage(&self) -> Option<&'static str> {
+        Some(
+            "https://docs.rosettacommons.org/docs/latest/application_documentation/analysis/score-commands",
+        )
+    }
+
+    fn default_args(&self) -> &'static [&'static str] {
+        &["score"]
+    }
+
     fn container_spec(&self, mut args: Vec<String>) -> ContainerConfig {
         args.insert(0, "score".into());
         rosetta::ROSETTA.container_spec(args)
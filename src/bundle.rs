@@ -0,0 +1,418 @@
+use std::{collections::BTreeMap, fs};
+
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use yansi::Paint;
+
+use crate::{
+    app::{App, AppSpec},
+    config,
+    engine::{
+        ContainerEngine, RunOptions, cached_image_path, cached_pixi_evn_root, hpc_image_path,
+        pixi_envs_root,
+    },
+    image,
+    telemetry::{hash_file, timestamp},
+    util::{Command, dirs, yansi::PaintExt},
+};
+
+/// Name of the manifest at the root of a bundle.
+const MANIFEST: &str = "rc-bundle.json";
+
+/// Contents of a bundle: one file per app, holding a `docker save` archive, a `.sif` image or a
+/// tar of a Pixi environment.
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    rc_version: String,
+    engine: ContainerEngine,
+    created: String,
+    entries: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleEntry {
+    app: String,
+    /// Container image as runs reference it, pinned when `rc.lock` pins it, `None` for Pixi
+    /// environments
+    image: Option<String>,
+    /// Path of the file inside the bundle
+    file: Utf8PathBuf,
+    blake3: String,
+    /// Directory a Pixi environment was exported from, environments are not relocatable
+    #[serde(default)]
+    native_root: Option<Utf8PathBuf>,
+}
+
+/// Package what `rc install <app> -e <engine>` installs for each of `apps` into the tar file
+/// `output`, installing it first when needed, for hosts without internet access. Images are the
+/// ones runs in `working_dir` use, so `rc.lock` pins apply.
+pub fn export(
+    apps: Vec<App>,
+    engine: ContainerEngine,
+    output: &Utf8Path,
+    working_dir: &Utf8Path,
+) -> Result<()> {
+    if engine == ContainerEngine::Auto {
+        return Err(anyhow!(
+            "pass the engine to export for with `-e`, not `auto`"
+        ));
+    }
+
+    let stage = staging_dir("export")?;
+    let result = (|| -> Result<()> {
+        let mut entries = Vec::new();
+        for app in apps {
+            println!("Exporting {} for {engine}...", app.green());
+            entries.push(export_app(app.spec(), engine, &stage, working_dir)?);
+        }
+
+        let manifest = BundleManifest {
+            rc_version: env!("CARGO_PKG_VERSION").into(),
+            engine,
+            created: timestamp(std::time::SystemTime::now()),
+            entries,
+        };
+        fs::write(
+            stage.join(MANIFEST),
+            serde_json::to_string_pretty(&manifest)?,
+        )?;
+
+        // `-h` stores the `.sif` images linked into the staging directory instead of the links
+        Command::new("tar")
+            .args(["-c", "-h", "-f", output.as_str(), "-C", stage.as_str()])
+            .arg(MANIFEST)
+            .args(manifest.entries.iter().map(|e| e.file.as_str()))
+            .live()
+            .exec()
+            .with_context(|| format!("failed to write bundle {output}"))?;
+        Ok(())
+    })();
+    fs::remove_dir_all(&stage)?;
+    result?;
+
+    println!("Wrote {}", output.bright_blue());
+    Ok(())
+}
+
+fn export_app(
+    app: &dyn AppSpec,
+    engine: ContainerEngine,
+    stage: &Utf8Path,
+    working_dir: &Utf8Path,
+) -> Result<BundleEntry> {
+    if engine == ContainerEngine::None && app.pixi_recipe().is_none() {
+        return Err(anyhow!(
+            "{} has no native environment, export it for a container engine",
+            app.name()
+        ));
+    }
+    if engine == ContainerEngine::None && config::settings().native_prefix(app).is_some() {
+        return Err(anyhow!(
+            "{} runs from an existing installation, there is no environment to export",
+            app.name()
+        ));
+    }
+
+    let name = app.name().to_lowercase();
+    let (file, image, native_root) = match engine {
+        ContainerEngine::Docker | ContainerEngine::Podman => {
+            let image = image::run_image(app, None, working_dir)?;
+            let tool = engine.to_string();
+            if image_id(&tool, &image).is_none() {
+                Command::new(&tool)
+                    .args(["image", "pull", &image])
+                    .live()
+                    .exec()
+                    .with_context(|| format!("failed to pull {image}"))?;
+            }
+
+            // an archive of a digest reference loads without a name, so the tag is saved with it
+            // when it points at the same image
+            let mut references = vec![image.clone()];
+            let configured = config::settings().container_image(app);
+            if configured != image && image_id(&tool, &configured) == image_id(&tool, &image) {
+                references.push(configured);
+            }
+            let file = Utf8PathBuf::from(format!("images/{name}.tar"));
+            fs::create_dir_all(stage.join("images"))?;
+            Command::new(&tool)
+                .args(["save", "-o", stage.join(&file).as_str()])
+                .args(&references)
+                .live()
+                .exec()
+                .with_context(|| format!("failed to save {image}"))?;
+            (file, Some(image), None)
+        }
+        ContainerEngine::Singularity | ContainerEngine::Apptainer => {
+            let image = image::run_image(app, None, working_dir)?;
+            let options = RunOptions {
+                image: Some(image.clone()),
+                ..Default::default()
+            };
+            engine.engine().prepare(app, &options)?;
+            let source = hpc_image_path(&image);
+            let file_name = source
+                .file_name()
+                .ok_or_else(|| anyhow!("invalid image path {source}"))?;
+            let file = Utf8PathBuf::from("images").join(file_name);
+            fs::create_dir_all(stage.join("images"))?;
+            symlink(&source, &stage.join(&file))?;
+            (file, Some(image), None)
+        }
+        ContainerEngine::None => {
+            // environments only work from the path they were built in, so the bundle carries the
+            // one in the cache, which imports into the cache, even when a shared one is installed
+            engine.engine().install(app, false)?;
+            let root = cached_pixi_evn_root(app);
+            let (parent, dir) = root
+                .parent()
+                .zip(root.file_name())
+                .ok_or_else(|| anyhow!("invalid environment path {root}"))?;
+            let file = Utf8PathBuf::from(format!("native/{name}.tar"));
+            fs::create_dir_all(stage.join("native"))?;
+            Command::new("tar")
+                .args([
+                    "-c",
+                    "-f",
+                    stage.join(&file).as_str(),
+                    "-C",
+                    parent.as_str(),
+                    dir,
+                ])
+                .live()
+                .exec()
+                .with_context(|| format!("failed to pack {root}"))?;
+            (file, None, Some(root))
+        }
+        ContainerEngine::Auto => unreachable!("`auto` is rejected before exporting"),
+    };
+
+    Ok(BundleEntry {
+        app: app.name().into(),
+        image,
+        blake3: hash_file(&stage.join(&file))?,
+        file,
+        native_root,
+    })
+}
+
+/// Install the contents of the bundle at `bundle` into the cache, or into Docker/Podman with
+/// `load`, after verifying every checksum.
+pub fn import(bundle: &Utf8Path) -> Result<()> {
+    let stage = staging_dir("import")?;
+    let result = (|| -> Result<()> {
+        // the manifest is checked before the images and environments are unpacked
+        let members = members(bundle)?;
+        let member = |file: &Utf8Path| {
+            members
+                .get(file)
+                .ok_or_else(|| anyhow!("{bundle} does not contain {file}"))
+        };
+        let unpack = |names: Vec<&String>| {
+            Command::new("tar")
+                .args(["-x", "-f", bundle.as_str(), "-C", stage.as_str()])
+                .args(names)
+                .live()
+                .exec()
+                .with_context(|| format!("failed to unpack bundle {bundle}"))
+        };
+
+        let manifest = member(Utf8Path::new(MANIFEST))
+            .with_context(|| format!("{bundle} is not an rc bundle"))?;
+        unpack(vec![manifest])?;
+        let manifest = fs::read_to_string(stage.join(MANIFEST))?;
+        let manifest: BundleManifest = serde_json::from_str(&manifest)
+            .with_context(|| format!("invalid {MANIFEST} in {bundle}"))?;
+
+        let mut apps = Vec::new();
+        for entry in &manifest.entries {
+            let inside = entry
+                .file
+                .components()
+                .all(|c| matches!(c, camino::Utf8Component::Normal(_)));
+            if !inside {
+                return Err(anyhow!(
+                    "bundle entry {} points outside the bundle",
+                    entry.file
+                ));
+            }
+            apps.push(
+                App::iter()
+                    .find(|a| a.spec().name() == entry.app)
+                    .ok_or_else(|| anyhow!("bundle contains unknown app `{}`", entry.app))?,
+            );
+            if let Some(root) = &entry.native_root {
+                let target = pixi_envs_root().join(&entry.app);
+                if *root != target {
+                    return Err(anyhow!(
+                        "the {} environment was exported from {root} and would be installed into {target}, Pixi environments do not work from another path (set RC_CACHE_DIR so the two match)",
+                        entry.app
+                    ));
+                }
+            }
+        }
+
+        unpack(
+            manifest
+                .entries
+                .iter()
+                .map(|e| member(&e.file))
+                .collect::<Result<_>>()?,
+        )?;
+        for entry in &manifest.entries {
+            let path = stage.join(&entry.file);
+            if hash_file(&path)? != entry.blake3 {
+                return Err(anyhow!(
+                    "checksum mismatch for {} in {bundle}, the bundle is damaged",
+                    entry.file
+                ));
+            }
+        }
+
+        for (app, entry) in apps.into_iter().zip(&manifest.entries) {
+            println!("Importing {} for {}...", app.green(), manifest.engine);
+            import_entry(app.spec(), manifest.engine, entry, &stage.join(&entry.file))?;
+        }
+        Ok(())
+    })();
+    fs::remove_dir_all(&stage)?;
+    result
+}
+
+fn import_entry(
+    app: &dyn AppSpec,
+    engine: ContainerEngine,
+    entry: &BundleEntry,
+    path: &Utf8Path,
+) -> Result<()> {
+    let image = || {
+        entry
+            .image
+            .clone()
+            .ok_or_else(|| anyhow!("bundle entry {} has no image", entry.file))
+    };
+
+    match engine {
+        ContainerEngine::Docker | ContainerEngine::Podman => {
+            Command::new(engine.to_string())
+                .args(["load", "-i", path.as_str()])
+                .live()
+                .exec()
+                .with_context(|| format!("failed to load {}", image().unwrap_or_default()))?;
+            let image = image()?;
+            if image_id(&engine.to_string(), &image).is_none() {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "{engine} did not keep the reference {image} while loading, runs pinned to it by rc.lock will try to pull it"
+                    )
+                    .orange()
+                );
+            }
+        }
+        ContainerEngine::Singularity | ContainerEngine::Apptainer => {
+            let target = cached_image_path(&image()?);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // the staging directory is inside the cache, so this is a rename on one filesystem
+            fs::rename(path, &target)
+                .or_else(|_| fs::copy(path, &target).map(|_| ()))
+                .with_context(|| format!("failed to install {target}"))?;
+            println!("Installed {}", target.bright_blue());
+        }
+        ContainerEngine::None => {
+            // unpack next to the archive first, a working environment is only replaced by a
+            // complete one
+            let unpacked = path.with_file_name(format!("{}.unpacked", app.name()));
+            fs::create_dir_all(&unpacked)?;
+            Command::new("tar")
+                .args(["-x", "-f", path.as_str(), "-C", unpacked.as_str()])
+                .live()
+                .exec()
+                .with_context(|| format!("failed to unpack the {} environment", app.name()))?;
+            let source = unpacked.join(app.name());
+            if !source.is_dir() {
+                return Err(anyhow!(
+                    "bundle entry {} does not hold the {} environment",
+                    entry.file,
+                    app.name()
+                ));
+            }
+
+            let root = pixi_envs_root();
+            let target = root.join(app.name());
+            fs::create_dir_all(&root)?;
+            let old = root.join(format!(".{}.old-{}", app.name(), std::process::id()));
+            if target.exists() {
+                fs::rename(&target, &old)?;
+            }
+            // the staging directory is inside the cache, so this is a rename on one filesystem
+            if let Err(e) = fs::rename(&source, &target) {
+                if old.exists() {
+                    fs::rename(&old, &target)?;
+                }
+                return Err(e).with_context(|| format!("failed to install {target}"));
+            }
+            if old.exists() {
+                fs::remove_dir_all(&old)?;
+            }
+            println!("Installed {}", target.bright_blue());
+        }
+        ContainerEngine::Auto => return Err(anyhow!("bundle was exported for `auto`")),
+    }
+    Ok(())
+}
+
+/// Files in the tar file `bundle` by their path inside the bundle, mapped to their name in the
+/// archive, which is `./<path>` for archives created with `tar -C <dir> .`.
+fn members(bundle: &Utf8Path) -> Result<BTreeMap<Utf8PathBuf, String>> {
+    let listing = Command::new("tar")
+        .args(["-t", "-f", bundle.as_str()])
+        .silent()
+        .try_call();
+    if !listing.success {
+        return Err(anyhow!(
+            "failed to read bundle {bundle}: {}",
+            listing.stderr.trim()
+        ));
+    }
+    Ok(listing
+        .stdout
+        .lines()
+        .map(|name| (name.trim_start_matches("./").into(), name.to_string()))
+        .collect())
+}
+
+/// Id of the local image `image` of the Docker compatible `tool`, `None` when it is not present.
+fn image_id(tool: &str, image: &str) -> Option<String> {
+    let result = Command::new(tool)
+        .args(["image", "inspect", "--format", "{{.Id}}", image])
+        .try_call();
+    result.success.then(|| result.stdout.trim().to_string())
+}
+
+/// Fresh directory in the cache for assembling or unpacking a bundle, images moved out of it stay
+/// on the cache filesystem.
+fn staging_dir(purpose: &str) -> Result<Utf8PathBuf> {
+    let dir = dirs::cache_root().join(format!(".bundle-{purpose}-{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+#[cfg(unix)]
+fn symlink(source: &Utf8Path, link: &Utf8Path) -> Result<()> {
+    std::os::unix::fs::symlink(source, link)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn symlink(source: &Utf8Path, link: &Utf8Path) -> Result<()> {
+    fs::copy(source, link)?;
+    Ok(())
+}
@@ -0,0 +1,375 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use yansi::Paint;
+
+use crate::{
+    app::App,
+    config,
+    engine::{cached_image_path, digest_file, hpc_images_root, pixi_envs_root},
+    image::ImageRef,
+    util::{dirs, yansi::PaintExt},
+};
+
+/// Marker touched inside directory artifacts (native environments) every time they are used.
+const LAST_USED_FILE: &str = ".last-used.rc";
+
+/// A single cached image or environment under the cache root.
+#[derive(Debug, Serialize)]
+pub struct Artifact {
+    pub path: Utf8PathBuf,
+    /// `hpc` for Singularity/Apptainer `.sif` images, `native` for Pixi environments
+    pub kind: &'static str,
+    /// Owning app, `None` when no app of this rc version maps to the artifact
+    pub app: Option<String>,
+    pub size: u64,
+    #[serde(serialize_with = "serialize_time")]
+    pub last_used: SystemTime,
+    /// Not referenced by any app (or configured image) of this rc version
+    pub stale: bool,
+}
+
+/// Selection for `rc cache prune`, artifacts must match every criterion that is set.
+#[derive(Debug, Default)]
+pub struct PruneFilter {
+    pub older_than: Option<Duration>,
+    pub apps: Vec<App>,
+    pub stale: bool,
+}
+
+/// Record that `path` (a `.sif` file or an environment directory) was just used.
+pub fn touch(path: &Utf8Path) -> Result<()> {
+    if path.is_dir() {
+        fs::write(path.join(LAST_USED_FILE), "")?;
+    } else if path.is_file() {
+        fs::File::options()
+            .append(true)
+            .open(path)?
+            .set_modified(SystemTime::now())?;
+    }
+    Ok(())
+}
+
+/// Scan the cache root for `.sif` images and native environments.
+pub fn scan() -> Result<Vec<Artifact>> {
+    let settings = config::settings();
+    let mut artifacts = Vec::new();
+
+    let images_root = hpc_images_root();
+    if images_root.exists() {
+        for entry in images_root.read_dir_utf8()? {
+            let path = entry?.into_path();
+            if path.extension() != Some("sif") {
+                continue;
+            }
+            let app = App::iter()
+                .find(|a| {
+                    let image = settings.container_image(a.spec());
+                    cached_image_path(&image) == path || is_pinned_image_of(&path, &image)
+                })
+                .map(|a| a.to_string());
+            artifacts.push(Artifact {
+                size: fs::metadata(&path)?.len(),
+                last_used: last_used(&path)?,
+                stale: app.is_none(),
+                app,
+                kind: "hpc",
+                path,
+            });
+        }
+    }
+
+    let envs_root = pixi_envs_root();
+    if envs_root.exists() {
+        for entry in envs_root.read_dir_utf8()? {
+            let path = entry?.into_path();
+            if !path.is_dir() {
+                continue;
+            }
+            let app = App::iter()
+                .find(|a| {
+                    a.spec().pixi_recipe().is_some() && Some(a.spec().name()) == path.file_name()
+                })
+                .map(|a| a.to_string());
+            artifacts.push(Artifact {
+                size: dir_size(&path)?,
+                last_used: last_used(&path)?,
+                stale: app.is_none(),
+                app,
+                kind: "native",
+                path,
+            });
+        }
+    }
+
+    artifacts.sort_by(|a, b| (a.kind, &a.path).cmp(&(b.kind, &b.path)));
+    Ok(artifacts)
+}
+
+/// Whether `path` is a digest-named image (written for `rc.lock` pins) of the repository of `image`.
+fn is_pinned_image_of(path: &Utf8Path, image: &str) -> bool {
+    let repository = ImageRef::parse(image).repository.replace('/', "-");
+    path.file_stem()
+        .and_then(|stem| stem.split_once('@'))
+        .is_some_and(|(repo, _)| repo == repository)
+}
+
+fn last_used(path: &Utf8Path) -> Result<SystemTime> {
+    let marker = path.join(LAST_USED_FILE);
+    let path = if path.is_dir() && marker.exists() {
+        marker.as_path()
+    } else {
+        path
+    };
+    Ok(fs::metadata(path)?.modified()?)
+}
+
+fn dir_size(path: &Utf8Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in path.read_dir_utf8()? {
+        let entry = entry?;
+        let meta = entry.path().symlink_metadata()?;
+        size += if meta.is_dir() {
+            dir_size(entry.path())?
+        } else {
+            meta.len()
+        };
+    }
+    Ok(size)
+}
+
+impl PruneFilter {
+    fn matches(&self, artifact: &Artifact, now: SystemTime) -> bool {
+        let old_enough = self.older_than.is_none_or(|age| {
+            now.duration_since(artifact.last_used)
+                .is_ok_and(|idle| idle >= age)
+        });
+        let app_matches = self.apps.is_empty()
+            || self
+                .apps
+                .iter()
+                .any(|a| artifact.app.as_deref() == Some(&a.to_string()));
+
+        old_enough && app_matches && (!self.stale || artifact.stale)
+    }
+}
+
+pub fn cache_ls(json: bool) -> Result<()> {
+    let artifacts = scan()?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&artifacts)?);
+        return Ok(());
+    }
+
+    println!("{} {}", "Cache root:".bold(), dirs::cache_root());
+    if artifacts.is_empty() {
+        println!("No cached images or environments");
+        return Ok(());
+    }
+
+    let now = SystemTime::now();
+    println!(
+        "{}",
+        format!(
+            "{:<8} {:<18} {:>10} {:>12}  {}",
+            "ENGINE", "APP", "SIZE", "LAST USED", "PATH"
+        )
+        .dim()
+    );
+    for a in &artifacts {
+        let app = match &a.app {
+            Some(app) => app.green().to_string(),
+            None => "stale".orange().to_string(),
+        };
+        println!(
+            "{:<8} {:<18} {:>10} {:>12}  {}",
+            a.kind,
+            app,
+            format_size(a.size),
+            format_age(now, a.last_used),
+            a.path
+        );
+    }
+    Ok(())
+}
+
+pub fn cache_du(json: bool) -> Result<()> {
+    let artifacts = scan()?;
+
+    let total: u64 = artifacts.iter().map(|a| a.size).sum();
+    let by_kind = |kind: &str| -> u64 {
+        artifacts
+            .iter()
+            .filter(|a| a.kind == kind)
+            .map(|a| a.size)
+            .sum()
+    };
+    let stale: u64 = artifacts.iter().filter(|a| a.stale).map(|a| a.size).sum();
+
+    if json {
+        let output = serde_json::json!({
+            "cache_root": dirs::cache_root(),
+            "hpc": by_kind("hpc"),
+            "native": by_kind("native"),
+            "stale": stale,
+            "total": total,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    println!("{} {}", "Cache root:".bold(), dirs::cache_root());
+    println!("  {:<8} {:>10}", "hpc", format_size(by_kind("hpc")));
+    println!("  {:<8} {:>10}", "native", format_size(by_kind("native")));
+    println!("  {:<8} {:>10}", "stale".orange(), format_size(stale));
+    println!("  {:<8} {:>10}", "total".bold(), format_size(total).bold());
+    Ok(())
+}
+
+pub fn cache_prune(filter: PruneFilter, dry_run: bool) -> Result<()> {
+    let now = SystemTime::now();
+    let selected: Vec<Artifact> = scan()?
+        .into_iter()
+        .filter(|a| filter.matches(a, now))
+        .collect();
+
+    if selected.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+
+    let mut freed = 0;
+    for a in &selected {
+        if dry_run {
+            println!("Would remove {} ({})", a.path, format_size(a.size));
+        } else {
+            println!(
+                "{}",
+                format!("Removing {} ({})", a.path, format_size(a.size)).orange()
+            );
+            if a.path.is_dir() {
+                fs::remove_dir_all(&a.path)?;
+            } else {
+                fs::remove_file(&a.path)?;
+                let _ = fs::remove_file(digest_file(&a.path));
+            }
+        }
+        freed += a.size;
+    }
+
+    println!(
+        "{} {}",
+        if dry_run { "Would free" } else { "Freed" },
+        format_size(freed).bold()
+    );
+    Ok(())
+}
+
+fn serialize_time<S: serde::Serializer>(t: &SystemTime, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&humantime::format_rfc3339_seconds(*t).to_string())
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_age(now: SystemTime, t: SystemTime) -> String {
+    let secs = now.duration_since(t).unwrap_or_default().as_secs();
+    match secs {
+        0..60 => "just now".into(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(app: Option<&str>, idle: Duration, now: SystemTime) -> Artifact {
+        Artifact {
+            path: "/cache/x".into(),
+            kind: "hpc",
+            stale: app.is_none(),
+            app: app.map(String::from),
+            size: 1,
+            last_used: now - idle,
+        }
+    }
+
+    #[test]
+    fn prune_filter_criteria_are_combined() {
+        let now = SystemTime::now();
+        let day = Duration::from_secs(86400);
+
+        let old_rosetta = artifact(Some("rosetta"), 40 * day, now);
+        let new_rosetta = artifact(Some("rosetta"), day, now);
+        let old_stale = artifact(None, 40 * day, now);
+
+        let by_age = PruneFilter {
+            older_than: Some(30 * day),
+            ..Default::default()
+        };
+        assert!(by_age.matches(&old_rosetta, now));
+        assert!(!by_age.matches(&new_rosetta, now));
+        assert!(by_age.matches(&old_stale, now));
+
+        let by_app = PruneFilter {
+            apps: vec![App::Rosetta],
+            ..Default::default()
+        };
+        assert!(by_app.matches(&new_rosetta, now));
+        assert!(!by_app.matches(&old_stale, now));
+
+        let stale_and_old = PruneFilter {
+            older_than: Some(30 * day),
+            stale: true,
+            ..Default::default()
+        };
+        assert!(!stale_and_old.matches(&old_rosetta, now));
+        assert!(stale_and_old.matches(&old_stale, now));
+    }
+
+    #[test]
+    fn pinned_images_belong_to_their_repository() {
+        let image = "rosettacommons/rosetta:serial";
+        assert!(is_pinned_image_of(
+            Utf8Path::new("/cache/hpc/rosettacommons-rosetta@sha256:abc.sif"),
+            image
+        ));
+        assert!(!is_pinned_image_of(
+            Utf8Path::new("/cache/hpc/rosettacommons-rfdiffusion@sha256:abc.sif"),
+            image
+        ));
+        assert!(!is_pinned_image_of(
+            Utf8Path::new("/cache/hpc/rosettacommons-rosetta:serial.sif"),
+            image
+        ));
+    }
+
+    #[test]
+    fn sizes_are_human_readable() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use yansi::Paint;
+
+use crate::{app::App, config, engine::ContainerEngine, util::yansi::PaintExt};
+
+/// Install state of an app for one engine.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, strum::IntoStaticStr)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+enum InstallStatus {
+    Installed,
+    NotInstalled,
+    /// The engine is not usable on this host
+    Unavailable,
+    /// The app cannot run on this engine (native without a Pixi recipe)
+    Unsupported,
+}
+
+#[derive(Serialize)]
+struct EngineStatus {
+    engine: ContainerEngine,
+    status: InstallStatus,
+}
+
+/// Everything `rc apps`/`rc info` report about an app.
+#[derive(Serialize)]
+struct AppInfo {
+    name: String,
+    description: String,
+    homepage: Option<&'static str>,
+    image: String,
+    native: bool,
+    gpu: bool,
+    required_args: &'static [&'static str],
+    default_args: &'static [&'static str],
+    engines: Vec<EngineStatus>,
+}
+
+/// Engines whose install status is reported, Apptainer shares its images with Singularity but is
+/// listed separately since either may be missing.
+fn engines() -> impl Iterator<Item = ContainerEngine> {
+    ContainerEngine::iter().filter(|e| *e != ContainerEngine::Auto)
+}
+
+fn app_info(app: App, usable: &[ContainerEngine]) -> AppInfo {
+    let spec = app.spec();
+    let help = app
+        .to_possible_value()
+        .and_then(|v| v.get_help().map(|h| h.to_string()))
+        .unwrap_or_default();
+    // the doc comments end with the homepage, which is reported separately
+    let description = match help.find(" http") {
+        Some(i) => help[..i].to_string(),
+        None => help,
+    };
+
+    let engines = engines()
+        .map(|engine| {
+            let status = if engine == ContainerEngine::None && spec.pixi_recipe().is_none() {
+                InstallStatus::Unsupported
+            } else if !usable.contains(&engine) {
+                InstallStatus::Unavailable
+            } else if engine.engine().is_installed(spec) {
+                InstallStatus::Installed
+            } else {
+                InstallStatus::NotInstalled
+            };
+            EngineStatus { engine, status }
+        })
+        .collect();
+
+    AppInfo {
+        name: app.to_string(),
+        description,
+        homepage: spec.homepage(),
+        image: config::settings().container_image(spec),
+        native: spec.pixi_recipe().is_some(),
+        gpu: spec.wants_gpu(),
+        required_args: spec.required_args(),
+        default_args: spec.default_args(),
+        engines,
+    }
+}
+
+fn usable_engines() -> Vec<ContainerEngine> {
+    engines().filter(|e| e.is_usable()).collect()
+}
+
+fn status(status: InstallStatus) -> yansi::Painted<&'static str> {
+    let text: &'static str = status.into();
+    match status {
+        InstallStatus::Installed => text.green(),
+        InstallStatus::NotInstalled => text.orange(),
+        InstallStatus::Unavailable | InstallStatus::Unsupported => text.dim(),
+    }
+}
+
+/// `rc apps`: every app with its image and install status per engine.
+pub fn apps(json: bool) -> Result<()> {
+    let usable = usable_engines();
+    let apps: Vec<AppInfo> = App::iter().map(|a| app_info(a, &usable)).collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&apps)?);
+        return Ok(());
+    }
+
+    print!("{}", format!("{:<20}", "APP").dim());
+    for engine in engines() {
+        print!(
+            "{}",
+            format!(" {:<14}", engine.to_string().to_uppercase()).dim()
+        );
+    }
+    println!("{}", " DESCRIPTION".dim());
+
+    for app in &apps {
+        print!("{:<20}", app.name.green());
+        for e in &app.engines {
+            print!(" {:<14}", status(e.status));
+        }
+        println!(" {}", app.description);
+    }
+    Ok(())
+}
+
+/// `rc info <app>`: details of a single app.
+pub fn info(app: App, json: bool) -> Result<()> {
+    let info = app_info(app, &usable_engines());
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    println!("{} {}", "App:".bold(), info.name.green());
+    println!("{} {}", "Description:".bold(), info.description);
+    if let Some(homepage) = info.homepage {
+        println!("{} {}", "Homepage:".bold(), homepage.bright_blue());
+    }
+    println!("{} {}", "Image:".bold(), info.image);
+    println!(
+        "{} {}",
+        "GPU:".bold(),
+        if info.gpu { "wanted" } else { "not needed" }
+    );
+    println!(
+        "{} {}",
+        "Native:".bold(),
+        if info.native {
+            "supported"
+        } else {
+            "not supported"
+        }
+    );
+
+    let usage = std::iter::once(info.name.as_str())
+        .chain(info.required_args.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!("{} rc run {usage} [ARGS]...", "Usage:".bold());
+
+    if !info.default_args.is_empty() {
+        println!("{}", "Default arguments:".bold());
+        for arg in info.default_args {
+            println!("  {arg}");
+        }
+    }
+
+    println!("{}", "Engines:".bold());
+    for e in &info.engines {
+        println!("  {:<12} {}", e.engine.to_string(), status(e.status));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info_splits_description_and_homepage() {
+        let info = app_info(App::Colabfold, &[]);
+        assert_eq!(info.description, "Run the ColabFold command");
+        assert_eq!(
+            info.homepage,
+            Some("https://github.com/sokrypton/ColabFold")
+        );
+        assert_eq!(info.default_args, ["colabfold_batch"]);
+    }
+
+    #[test]
+    fn status_of_unusable_and_unsupported_engines() {
+        let info = app_info(App::Patchman, &[]);
+        assert!(!info.native);
+        for e in &info.engines {
+            let expected = match e.engine {
+                ContainerEngine::None => InstallStatus::Unsupported,
+                _ => InstallStatus::Unavailable,
+            };
+            assert_eq!(e.status, expected, "{}", e.engine);
+        }
+    }
+
+    #[test]
+    fn every_app_has_a_homepage_and_description() {
+        for app in App::iter() {
+            let info = app_info(app, &[]);
+            assert!(info.homepage.is_some(), "{app}");
+            assert!(!info.description.is_empty(), "{app}");
+        }
+    }
+}
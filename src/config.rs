@@ -1,14 +1,311 @@
+mod keys;
+mod project;
+
+use std::{collections::BTreeMap, fmt, fs, sync::OnceLock};
+
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
 use serde::Serialize;
 use strum::IntoEnumIterator;
+use toml_edit::{DocumentMut, Item, Table};
 use yansi::Paint;
 
 use crate::{
-    app::App,
-    engine::{hpc_image_path, pixi_evn_root},
+    app::{App, AppSpec},
+    engine::{ContainerEngine, hpc_image_path, pixi_evn_root},
+    image::ImageRef,
+    util::{self, dirs},
 };
 
+pub use keys::Key;
+pub use project::{Project, ProjectSection};
+
+/// Where an effective configuration value came from, lowest precedence first.
+/// Command line flags take precedence over all of them and are applied by the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Default,
+    File,
+    Env(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File => write!(f, "file"),
+            Origin::Env(var) => write!(f, "env {var}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting {
+    pub value: String,
+    pub origin: Origin,
+}
+
+/// Effective configuration: built-in defaults < config file < `RC_*` environment variables.
+#[derive(Debug)]
+pub struct Settings {
+    values: BTreeMap<String, Setting>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Load the effective configuration, reporting malformed config files or environment values.
+pub fn load() -> Result<&'static Settings> {
+    if let Some(settings) = SETTINGS.get() {
+        return Ok(settings);
+    }
+    let settings = Settings::load()?;
+    Ok(SETTINGS.get_or_init(|| settings))
+}
+
+/// Effective configuration; `main` calls [`load`] first so errors surface before any work starts.
+pub fn settings() -> &'static Settings {
+    load().expect("failed to load rc configuration")
+}
+
+/// Path of the user configuration file, `RC_CONFIG` overrides the default location.
+pub fn config_file() -> Utf8PathBuf {
+    std::env::var("RC_CONFIG")
+        .ok()
+        .filter(|p| !p.is_empty())
+        .map(Utf8PathBuf::from)
+        .unwrap_or_else(|| dirs::config_root().join("config.toml"))
+}
+
+impl Settings {
+    fn load() -> Result<Self> {
+        let keys = keys::all();
+
+        let mut values: BTreeMap<String, Setting> = keys
+            .iter()
+            .filter_map(|k| {
+                k.default.clone().map(|value| {
+                    let setting = Setting {
+                        value,
+                        origin: Origin::Default,
+                    };
+                    (k.name.clone(), setting)
+                })
+            })
+            .collect();
+
+        let path = config_file();
+        if path.exists() {
+            for (name, value) in flatten(&read_document(&path)?) {
+                let Ok(key) = name.parse::<Key>() else {
+                    eprintln!(
+                        "{}",
+                        format!("Ignoring unknown configuration key `{name}` in {path}").yellow()
+                    );
+                    continue;
+                };
+                key.kind
+                    .validate(&value)
+                    .with_context(|| format!("invalid value for `{name}` in {path}"))?;
+                let origin = Origin::File;
+                values.insert(name, Setting { value, origin });
+            }
+        }
+
+        for key in &keys {
+            if let Ok(value) = std::env::var(&key.env) {
+                key.kind.validate(&value).with_context(|| {
+                    format!("invalid value in environment variable {}", key.env)
+                })?;
+                let origin = Origin::Env(key.env.clone());
+                values.insert(key.name.clone(), Setting { value, origin });
+            }
+        }
+
+        Ok(Settings { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Setting> {
+        self.values.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Setting)> {
+        self.values.iter()
+    }
+
+    fn value(&self, key: &str) -> &str {
+        &self
+            .values
+            .get(key)
+            .unwrap_or_else(|| panic!("configuration key `{key}` has no value"))
+            .value
+    }
+
+    /// Engine used when none is given on the command line.
+    pub fn engine(&self) -> ContainerEngine {
+        keys::parse_engine(self.value("engine.default"))
+            .expect("engine.default is validated on load")
+    }
+
+    /// Engines probed, in order, when the engine is `auto`.
+    pub fn engine_priority(&self) -> Vec<ContainerEngine> {
+        keys::parse_engines(self.value("engine.priority"))
+            .expect("engine.priority is validated on load")
+    }
+
+    /// Engine for `app` when none is given on the command line: a project file engine overrides
+    /// the user config file but not an `RC_ENGINE` environment override.
+    pub fn engine_for(&self, app: &dyn AppSpec, project: Option<&Project>) -> ContainerEngine {
+        let from_env = matches!(
+            self.get("engine.default").map(|s| &s.origin),
+            Some(Origin::Env(_))
+        );
+        match project.and_then(|p| p.engine(app)) {
+            Some(engine) if !from_env => engine,
+            _ => self.engine(),
+        }
+    }
+
+    /// Whether Podman runs relabel every mount for SELinux, see `podman.relabel_mounts`.
+    pub fn podman_relabel_mounts(&self) -> bool {
+        keys::parse_bool(self.value("podman.relabel_mounts"))
+            .expect("podman.relabel_mounts is validated on load")
+    }
+
+    pub fn cache_root(&self) -> Utf8PathBuf {
+        expand_home(self.value("cache.root"))
+    }
+
+    /// Site-wide installation root from `shared.root`, if configured.
+    pub fn shared_root(&self) -> Option<Utf8PathBuf> {
+        self.get("shared.root").map(|s| expand_home(&s.value))
+    }
+
+    /// Existing installation of `app` registered with `apps.<app>.prefix`, used by native runs.
+    pub fn native_prefix(&self, app: &dyn AppSpec) -> Option<Utf8PathBuf> {
+        self.get(&app_key(app, "prefix"))
+            .map(|s| expand_home(&s.value))
+    }
+
+    /// Container image for `app`, honoring `apps.<app>.image`, `.tag` and `.digest` overrides.
+    pub fn container_image(&self, app: &dyn AppSpec) -> String {
+        let key = |field: &str| app_key(app, field);
+
+        let mut image = ImageRef::parse(
+            self.get(&key("image"))
+                .map_or(app.container_image(), |s| s.value.as_str()),
+        );
+        if let Some(tag) = self.get(&key("tag")) {
+            image.tag = Some(tag.value.clone());
+        }
+        if let Some(digest) = self.get(&key("digest")) {
+            image.digest = Some(digest.value.clone());
+        }
+        image.to_string()
+    }
+}
+
+fn expand_home(path: &str) -> Utf8PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home().join(rest),
+        None => path.into(),
+    }
+}
+
+fn read_document(path: &Utf8PathBuf) -> Result<DocumentMut> {
+    fs::read_to_string(path)?
+        .parse::<DocumentMut>()
+        .with_context(|| format!("failed to parse config file {path}"))
+}
+
+/// Flatten nested TOML tables into dotted keys, scalar values are converted to strings.
+fn flatten(doc: &DocumentMut) -> Vec<(String, String)> {
+    fn walk(prefix: &str, table: &Table, out: &mut Vec<(String, String)>) {
+        for (k, item) in table.iter() {
+            let name = if prefix.is_empty() {
+                k.to_string()
+            } else {
+                format!("{prefix}.{k}")
+            };
+            match item {
+                Item::Table(t) => walk(&name, t, out),
+                Item::Value(toml_edit::Value::InlineTable(t)) => {
+                    walk(&name, &t.clone().into_table(), out)
+                }
+                Item::Value(v) => out.push((name, scalar_to_string(v))),
+                Item::ArrayOfTables(_) | Item::None => {}
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    walk("", doc.as_table(), &mut out);
+    out
+}
+
+fn scalar_to_string(value: &toml_edit::Value) -> String {
+    match value {
+        toml_edit::Value::String(s) => s.value().clone(),
+        toml_edit::Value::Array(a) => a.iter().map(scalar_to_string).collect::<Vec<_>>().join(","),
+        other => other.to_string().trim().to_string(),
+    }
+}
+
+fn set_value(doc: &mut DocumentMut, key: &str, value: &str) -> Result<()> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().expect("key is not empty");
+
+    let mut table = doc.as_table_mut();
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(|| {
+                let mut t = Table::new();
+                t.set_implicit(true);
+                Item::Table(t)
+            })
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("`{part}` in `{key}` is not a table in the config file"))?;
+    }
+    table.insert(last, toml_edit::value(value));
+    Ok(())
+}
+
+/// Remove `key` from the document and drop any tables left empty. Returns false if not present.
+fn remove_value(doc: &mut DocumentMut, key: &str) -> bool {
+    fn remove(table: &mut Table, parts: &[&str]) -> bool {
+        match parts {
+            [] => false,
+            [last] => table.remove(last).is_some(),
+            [head, rest @ ..] => {
+                let Some(sub) = table.get_mut(head).and_then(Item::as_table_mut) else {
+                    return false;
+                };
+                let removed = remove(sub, rest);
+                if sub.is_empty() {
+                    table.remove(head);
+                }
+                removed
+            }
+        }
+    }
+
+    let parts: Vec<&str> = key.split('.').collect();
+    remove(doc.as_table_mut(), &parts)
+}
+
+#[derive(Serialize)]
+struct ProjectInfo {
+    path: String,
+    apps: BTreeMap<String, ProjectSection>,
+}
+
 #[derive(Serialize)]
 struct Config {
+    config_file: String,
+    project: Option<ProjectInfo>,
+    settings: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origins: Option<BTreeMap<String, String>>,
     apps: Vec<AppInfo>,
 }
 
@@ -20,15 +317,41 @@ struct AppInfo {
     native_root: Option<String>,
 }
 
-pub fn config_show(json: bool) -> std::result::Result<(), anyhow::Error> {
+pub fn config_show(json: bool, origin: bool, working_dir: &Utf8Path) -> Result<()> {
+    let settings = load()?;
+    let project = Project::discover(working_dir)?;
+
     let config = Config {
+        config_file: config_file().into(),
+        project: project.as_ref().map(|p| ProjectInfo {
+            path: p.path.to_string(),
+            apps: App::iter()
+                .map(|app| (app.to_string(), p.merged(app.spec())))
+                .collect(),
+        }),
+        settings: settings
+            .iter()
+            .map(|(k, s)| (k.clone(), s.value.clone()))
+            .collect(),
+        origins: origin.then(|| {
+            settings
+                .iter()
+                .map(|(k, s)| (k.clone(), s.origin.to_string()))
+                .collect()
+        }),
         apps: App::iter()
             .map(App::spec)
-            .map(|a| AppInfo {
-                name: a.name().to_lowercase(),
-                container_image: a.container_image().into(),
-                hpc_image_path: hpc_image_path(a.container_image()).into(),
-                native_root: a.pixi_recipe().map(|_| pixi_evn_root(a).into()),
+            .map(|a| {
+                let image = settings.container_image(a);
+                AppInfo {
+                    name: a.name().to_lowercase(),
+                    hpc_image_path: hpc_image_path(&image).into(),
+                    container_image: image,
+                    native_root: settings
+                        .native_prefix(a)
+                        .or_else(|| a.pixi_recipe().map(|_| pixi_evn_root(a)))
+                        .map(Into::into),
+                }
             })
             .collect(),
     };
@@ -36,28 +359,270 @@ pub fn config_show(json: bool) -> std::result::Result<(), anyhow::Error> {
     if json {
         let output = serde_json::to_string_pretty(&config).unwrap();
         println!("{}", output);
+        return Ok(());
+    }
+
+    let origin_of = |key: &str| -> String {
+        if origin {
+            format!(" ({})", settings.get(key).unwrap().origin)
+                .dim()
+                .to_string()
+        } else {
+            String::new()
+        }
+    };
+
+    println!("{} {}", "Config file:".bold(), config.config_file);
+    println!();
+    println!("{}", "Settings:".bold());
+    for (key, value) in config
+        .settings
+        .iter()
+        .filter(|(k, _)| !k.starts_with("apps."))
+    {
+        println!("  {:<22} {}{}", key.green(), value, origin_of(key));
+    }
+    println!();
+
+    if let Some(project) = &config.project {
+        println!("{} {}", "Project file:".bold(), project.path);
+        for (app, section) in project.apps.iter().filter(|(_, s)| !s.is_empty()) {
+            println!("  {} {}", "•".bold(), app.green().bold());
+            if let Some(engine) = section.engine {
+                println!("    {:<20} {}", "Engine:".dim(), engine);
+            }
+            if !section.args.is_empty() {
+                println!("    {:<20} {}", "Extra Args:".dim(), section.args.join(" "));
+            }
+            for mount in &section.mounts {
+                println!("    {:<20} {}", "Mount:".dim(), mount);
+            }
+            for (key, value) in &section.env {
+                println!("    {:<20} {key}={value}", "Env:".dim());
+            }
+        }
+        println!();
+    }
+
+    println!("{}", "Apps:".bold());
+    for app in &config.apps {
+        println!(
+            "  {} {}",
+            "•".bold(),
+            app.name.to_lowercase().green().bold()
+        );
+        println!(
+            "    {:<20} {}{}",
+            "Container Image:".dim(),
+            app.container_image,
+            origin_of(&format!("apps.{}.image", app.name))
+        );
+        println!("    {:<20} {}", "HPC Image Path:".dim(), app.hpc_image_path);
+        println!(
+            "    {:<20} {}",
+            "Native Root:".dim(),
+            app.native_root.as_deref().unwrap_or("—")
+        );
+        println!();
+    }
+
+    Ok(())
+}
+
+pub fn config_get(key: &str, json: bool) -> Result<()> {
+    let key: Key = key.parse()?;
+    let setting = load()?
+        .get(&key.name)
+        .ok_or_else(|| anyhow!("`{}` is not set", key.name))?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "key": key.name, "value": setting.value, "origin": setting.origin.to_string() })
+        );
+    } else {
+        println!("{}", setting.value);
+    }
+    Ok(())
+}
+
+pub fn config_set(key: &str, value: &str) -> Result<()> {
+    let key: Key = key.parse()?;
+    key.kind
+        .validate(value)
+        .with_context(|| format!("invalid value for `{}`", key.name))?;
+
+    let path = config_file();
+    let mut doc = if path.exists() {
+        read_document(&path)?
+    } else {
+        DocumentMut::new()
+    };
+
+    set_value(&mut doc, &key.name, value)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, doc.to_string())?;
+
+    println!(
+        "{} = {:?} {}",
+        key.name.green(),
+        value,
+        format!("({path})").dim()
+    );
+    if let Ok(env_value) = std::env::var(&key.env) {
+        eprintln!(
+            "{}",
+            format!(
+                "Note: environment variable {}={env_value} overrides this value",
+                key.env
+            )
+            .yellow()
+        );
+    }
+    Ok(())
+}
+
+pub fn config_unset(key: &str) -> Result<()> {
+    let key: Key = key.parse()?;
+
+    let path = config_file();
+    if !path.exists() {
+        println!("{} is not set in {path}", key.name);
+        return Ok(());
+    }
+
+    let mut doc = read_document(&path)?;
+    if remove_value(&mut doc, &key.name) {
+        fs::write(&path, doc.to_string())?;
+        println!("Removed {} from {path}", key.name.green());
     } else {
-        println!("{}", "Apps:".bold());
-        for app in &config.apps {
-            println!(
-                "  {} {}",
-                "•".bold(),
-                app.name.to_lowercase().green().bold()
-            );
-            println!(
-                "    {:<20} {}",
-                "Container Image:".dim(),
-                app.container_image
-            );
-            println!("    {:<20} {}", "HPC Image Path:".dim(), app.hpc_image_path);
-            println!(
-                "    {:<20} {}",
-                "Native Root:".dim(),
-                app.native_root.as_deref().unwrap_or("—")
-            );
-            println!();
+        println!("{} is not set in {path}", key.name);
+    }
+    Ok(())
+}
+
+pub fn config_edit() -> Result<()> {
+    let path = config_file();
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        fs::write(&path, template())?;
     }
 
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .find_map(|v| std::env::var(v).ok().filter(|e| !e.is_empty()))
+        .unwrap_or_else(|| "vi".into());
+
+    util::Command::shell(format!(
+        "{editor} {}",
+        shell_escape::escape(path.as_str().into())
+    ))
+    .live()
+    .exec()
+    .with_context(|| format!("editor `{editor}` exited with an error"))?;
+
+    Settings::load().map(|_| ())
+}
+
+pub fn config_path() -> Result<()> {
+    println!("{}", config_file());
     Ok(())
 }
+
+/// Commented starting point for a new config file listing every known key with its default.
+fn template() -> String {
+    let mut s = String::from(
+        "# rc configuration file\n# Precedence: defaults < this file < RC_* environment variables < command line flags\n",
+    );
+    for key in keys::all() {
+        s.push_str(&format!(
+            "\n# {} (env: {})\n# {} = {:?}\n",
+            key.about,
+            key.env,
+            key.name,
+            key.default.unwrap_or_default()
+        ));
+    }
+    s
+}
+
+/// Key `apps.<app>.<field>` of `app`. Keys use the lowercase app names of the command line, e.g.
+/// `apps.proteinmpnnscript.prefix`.
+fn app_key(app: &dyn AppSpec, field: &str) -> String {
+    format!("apps.{}.{field}", app.name().to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatten_nested_tables() {
+        let doc: DocumentMut = r#"
+            [engine]
+            default = "apptainer"
+
+            [apps.rosetta]
+            image = "rosettacommons/rosetta:mpi"
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            flatten(&doc),
+            vec![
+                ("engine.default".into(), "apptainer".into()),
+                (
+                    "apps.rosetta.image".into(),
+                    "rosettacommons/rosetta:mpi".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_and_remove_value_roundtrip() {
+        let mut doc = DocumentMut::new();
+        set_value(&mut doc, "apps.rosetta.image", "rosettacommons/rosetta:mpi").unwrap();
+        set_value(&mut doc, "engine.default", "none").unwrap();
+
+        assert_eq!(flatten(&doc).len(), 2);
+
+        assert!(remove_value(&mut doc, "apps.rosetta.image"));
+        assert!(!remove_value(&mut doc, "apps.rosetta.image"));
+        assert!(doc.get("apps").is_none(), "empty tables should be pruned");
+        assert_eq!(
+            flatten(&doc),
+            vec![("engine.default".into(), "none".into())]
+        );
+    }
+
+    #[test]
+    fn app_keys_match_the_known_keys() {
+        for app in App::iter() {
+            let image = app_key(app.spec(), "image");
+            assert_eq!(image, format!("apps.{app}.image"));
+            assert!(image.parse::<keys::Key>().is_ok(), "{image}");
+        }
+        let prefix = app_key(App::ProteinmpnnScript.spec(), "prefix");
+        assert!(prefix.parse::<keys::Key>().is_ok(), "{prefix}");
+    }
+
+    #[test]
+    fn unknown_key_is_rejected() {
+        assert!("engine.nope".parse::<Key>().is_err());
+        assert!("apps.rosetta.image".parse::<Key>().is_ok());
+    }
+
+    #[test]
+    fn engine_values_are_validated() {
+        let key: Key = "engine.default".parse().unwrap();
+        assert!(key.kind.validate("apptainer").is_ok());
+        assert!(key.kind.validate("podman-ish").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use strum::IntoEnumIterator;
+
+use crate::{app::App, engine::ContainerEngine, util::dirs};
+
+/// How a configuration value is validated before it is accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    String,
+    Path,
+    Engine,
+    /// Comma separated list of concrete engines
+    Engines,
+    /// `true` or `false`
+    Bool,
+}
+
+/// A known configuration key together with its environment override and built-in default.
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub name: String,
+    pub env: String,
+    pub kind: Kind,
+    pub default: Option<String>,
+    pub about: String,
+}
+
+impl Kind {
+    pub fn validate(self, value: &str) -> Result<()> {
+        match self {
+            Kind::String | Kind::Path if value.is_empty() => Err(anyhow!("value can not be empty")),
+            Kind::String | Kind::Path => Ok(()),
+            Kind::Engine => parse_engine(value).map(|_| ()),
+            Kind::Bool => parse_bool(value).map(|_| ()),
+            Kind::Engines => {
+                for engine in parse_engines(value)? {
+                    if engine == ContainerEngine::Auto {
+                        return Err(anyhow!("`auto` can not be part of the engine priority"));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn parse_engine(value: &str) -> Result<ContainerEngine> {
+    ContainerEngine::from_str(value.trim(), true).map_err(|_| {
+        anyhow!(
+            "invalid engine `{value}`, expected one of: {}",
+            ContainerEngine::iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
+
+pub fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(anyhow!(
+            "invalid value `{value}`, expected `true` or `false`"
+        )),
+    }
+}
+
+pub fn parse_engines(value: &str) -> Result<Vec<ContainerEngine>> {
+    value
+        .split(',')
+        .filter(|e| !e.trim().is_empty())
+        .map(parse_engine)
+        .collect()
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        all().into_iter().find(|k| k.name == name).ok_or_else(|| {
+            anyhow!("unknown configuration key `{name}`, see `rc config show` for the list of keys")
+        })
+    }
+}
+
+/// Every key understood by `rc`, in display order.
+pub fn all() -> Vec<Key> {
+    let mut keys = vec![
+        Key {
+            name: "engine.default".into(),
+            env: "RC_ENGINE".into(),
+            kind: Kind::Engine,
+            default: Some(ContainerEngine::Auto.to_string()),
+            about: "Container engine used when `-e` is not given".into(),
+        },
+        Key {
+            name: "engine.priority".into(),
+            env: "RC_ENGINE_PRIORITY".into(),
+            kind: Kind::Engines,
+            default: Some("docker,podman,apptainer,singularity,none".into()),
+            about: "Order in which `auto` probes for a usable engine".into(),
+        },
+        Key {
+            name: "cache.root".into(),
+            env: "RC_CACHE_DIR".into(),
+            kind: Kind::Path,
+            default: Some(dirs::default_cache_root().into()),
+            about: "Root directory for cached images and native environments".into(),
+        },
+        Key {
+            name: "shared.root".into(),
+            env: "RC_SHARED_ROOT".into(),
+            kind: Kind::Path,
+            default: None,
+            about: "Site-wide root of pre-installed images and native environments, used first"
+                .into(),
+        },
+        Key {
+            name: "podman.relabel_mounts".into(),
+            env: "RC_PODMAN_RELABEL_MOUNTS".into(),
+            kind: Kind::Bool,
+            default: Some("false".into()),
+            about: "Relabel `--mount`, project and auto-mounted host directories for SELinux (`:z`) in Podman runs, not just the working and scratch directories".into(),
+        },
+        Key {
+            name: "slurm.partition".into(),
+            env: "RC_SLURM_PARTITION".into(),
+            kind: Kind::String,
+            default: None,
+            about: "Slurm partition for `rc submit` when `--partition` is not given".into(),
+        },
+        Key {
+            name: "slurm.time".into(),
+            env: "RC_SLURM_TIME".into(),
+            kind: Kind::String,
+            default: None,
+            about: "Slurm wall time limit for `rc submit` when `--time` is not given".into(),
+        },
+        Key {
+            name: "slurm.account".into(),
+            env: "RC_SLURM_ACCOUNT".into(),
+            kind: Kind::String,
+            default: None,
+            about: "Slurm account charged by `rc submit` when `--account` is not given".into(),
+        },
+    ];
+
+    for app in App::iter() {
+        let env = |field: &str| format!("RC_{}_{field}", app.to_string().to_uppercase());
+        keys.extend([
+            Key {
+                name: format!("apps.{app}.image"),
+                env: env("IMAGE"),
+                kind: Kind::String,
+                default: Some(app.spec().container_image().into()),
+                about: format!("Container image used for the {app} app"),
+            },
+            Key {
+                name: format!("apps.{app}.tag"),
+                env: env("TAG"),
+                kind: Kind::String,
+                default: None,
+                about: format!("Overrides the tag of the {app} container image"),
+            },
+            Key {
+                name: format!("apps.{app}.digest"),
+                env: env("DIGEST"),
+                kind: Kind::String,
+                default: None,
+                about: format!("Pins the {app} container image to a digest, e.g. `sha256:...`"),
+            },
+        ]);
+        if app.spec().pixi_recipe().is_some() {
+            keys.push(Key {
+                name: format!("apps.{app}.prefix"),
+                env: env("PREFIX"),
+                kind: Kind::Path,
+                default: None,
+                about: format!(
+                    "Existing {app} installation (prefix, conda env or virtualenv) used by native runs instead of a Pixi environment"
+                ),
+            });
+        }
+    }
+
+    keys
+}
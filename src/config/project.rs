@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    app::{App, AppSpec},
+    engine::{ContainerEngine, Mount, RunOptions},
+};
+
+pub const PROJECT_FILE: &str = ".rc.toml";
+
+/// Settings shared by the whole project file and by each `[apps.<app>]` section.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectSection {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub engine: Option<ContainerEngine>,
+
+    /// Extra arguments appended after the arguments given on the command line, options the command
+    /// line already sets are left out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// Extra bind mounts, `host[:container][:ro]`, relative host paths are resolved against the
+    /// directory containing the project file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mounts: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+impl ProjectSection {
+    pub fn is_empty(&self) -> bool {
+        self.engine.is_none()
+            && self.args.is_empty()
+            && self.mounts.is_empty()
+            && self.env.is_empty()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    engine: Option<ContainerEngine>,
+
+    #[serde(default)]
+    args: Vec<String>,
+
+    #[serde(default)]
+    mounts: Vec<String>,
+
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+
+    #[serde(default)]
+    apps: BTreeMap<String, ProjectSection>,
+}
+
+/// Per-project settings from the nearest `.rc.toml` in the working directory or its parents.
+#[derive(Debug)]
+pub struct Project {
+    pub path: Utf8PathBuf,
+    common: ProjectSection,
+    apps: BTreeMap<String, ProjectSection>,
+}
+
+impl Project {
+    /// Find the nearest project file starting at `dir` and walking up to the filesystem root.
+    pub fn discover(dir: &Utf8Path) -> Result<Option<Self>> {
+        for d in dir.ancestors() {
+            let path = d.join(PROJECT_FILE);
+            if path.is_file() {
+                return Self::load(path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn load(path: Utf8PathBuf) -> Result<Self> {
+        let content = std::fs::read_to_string(&path)?;
+        let file: ProjectFile = toml_edit::de::from_str(&content)
+            .with_context(|| format!("failed to parse project file {path}"))?;
+
+        for name in file.apps.keys() {
+            if !App::iter().any(|a| a.to_string() == *name) {
+                return Err(anyhow!(
+                    "unknown app `{name}` in project file {path}, expected one of: {}",
+                    App::iter()
+                        .map(|a| a.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+
+        let project = Project {
+            path,
+            common: ProjectSection {
+                engine: file.engine,
+                args: file.args,
+                mounts: file.mounts,
+                env: file.env,
+            },
+            apps: file.apps,
+        };
+        for app in App::iter() {
+            project.run_options(app.spec())?;
+        }
+        Ok(project)
+    }
+
+    pub fn dir(&self) -> &Utf8Path {
+        self.path
+            .parent()
+            .expect("project file has a parent directory")
+    }
+
+    fn app_section(&self, app: &dyn AppSpec) -> Option<&ProjectSection> {
+        self.apps.get(&app.name().to_lowercase())
+    }
+
+    /// Project level engine for `app`, the `[apps.<app>]` section takes precedence.
+    pub fn engine(&self, app: &dyn AppSpec) -> Option<ContainerEngine> {
+        self.app_section(app)
+            .and_then(|s| s.engine)
+            .or(self.common.engine)
+    }
+
+    /// Extra arguments for `app`: project wide ones followed by app specific ones.
+    pub fn args(&self, app: &dyn AppSpec) -> Vec<String> {
+        self.sections(app)
+            .flat_map(|s| s.args.iter().cloned())
+            .collect()
+    }
+
+    /// Extra arguments for `app` to append after `given`, the command line arguments. Options and
+    /// `key=value` overrides that `given` already sets are dropped together with their values, so
+    /// the command line wins for apps where the last value of an option is the one used.
+    pub fn args_for(&self, app: &dyn AppSpec, given: &[String]) -> Vec<String> {
+        let given: Vec<&str> = given.iter().filter_map(|a| option_key(a)).collect();
+        let mut args = Vec::new();
+        let mut dropping = false;
+        for arg in self.args(app) {
+            match option_key(&arg) {
+                Some(key) => {
+                    dropping = given.contains(&key);
+                    if !dropping {
+                        args.push(arg);
+                    }
+                }
+                // values of a dropped `-opt value...`
+                None if dropping => {}
+                None => args.push(arg),
+            }
+        }
+        args
+    }
+
+    /// Mounts and environment for `app`, app specific values override project wide ones.
+    pub fn run_options(&self, app: &dyn AppSpec) -> Result<RunOptions> {
+        let mut options = RunOptions::default();
+        for section in self.sections(app) {
+            for spec in &section.mounts {
+                let mount = Mount::parse(spec, self.dir())
+                    .with_context(|| format!("in project file {}", self.path))?;
+                options.mounts.retain(|m| m.container != mount.container);
+                options.mounts.push(mount);
+            }
+            options.env.extend(section.env.clone());
+        }
+        Ok(options)
+    }
+
+    /// Effective settings for `app` with project wide and app specific values merged.
+    pub fn merged(&self, app: &dyn AppSpec) -> ProjectSection {
+        let options = self.run_options(app).unwrap_or_default();
+        ProjectSection {
+            engine: self.engine(app),
+            args: self.args(app),
+            mounts: options.mounts.iter().map(Mount::bind_spec).collect(),
+            env: options.env,
+        }
+    }
+
+    fn sections(&self, app: &dyn AppSpec) -> impl Iterator<Item = &ProjectSection> {
+        std::iter::once(&self.common).chain(self.app_section(app))
+    }
+}
+
+/// Option an argument sets: `-opt` and `--opt` for flags and options followed by their values,
+/// the part before `=` for `--opt=value` and Hydra `key=value` (`+key=value`) overrides.
+fn option_key(arg: &str) -> Option<&str> {
+    let key = match arg.split_once('=') {
+        Some((key, _)) => key.trim_start_matches('+'),
+        None if arg.starts_with('-') && !arg[1..].starts_with(|c: char| c.is_ascii_digit()) => arg,
+        None => return None,
+    };
+    (!key.trim_start_matches('-').is_empty()).then_some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    const PROJECT: &str = r#"
+        engine = "apptainer"
+        mounts = ["refs:/refs:ro"]
+        env = { HYDRA_FULL_ERROR = "1" }
+
+        [apps.foundry]
+        engine = "none"
+        args = ["diffusion_batch_size=1"]
+        mounts = ["/scratch/weights:/refs"]
+        env = { HYDRA_FULL_ERROR = "0", TORCH_HOME = "/torch" }
+    "#;
+
+    fn project(content: &str) -> (TempDir, Result<Option<Project>>) {
+        let dir = TempDir::new().unwrap();
+        dir.child(PROJECT_FILE).write_str(content).unwrap();
+        dir.child("a/b").create_dir_all().unwrap();
+
+        let nested = Utf8PathBuf::from_path_buf(dir.path().join("a/b")).unwrap();
+        let project = Project::discover(&nested);
+        (dir, project)
+    }
+
+    #[test]
+    fn discovers_project_file_in_parent() {
+        let (dir, project) = project(PROJECT);
+        let project = project.unwrap().expect("project file should be found");
+        assert_eq!(project.dir(), dir.path());
+    }
+
+    #[test]
+    fn app_section_overrides_common() {
+        let (_dir, project) = project(PROJECT);
+        let project = project.unwrap().unwrap();
+
+        let foundry = App::Foundry.spec();
+        assert_eq!(project.engine(foundry), Some(ContainerEngine::None));
+        assert_eq!(project.args(foundry), vec!["diffusion_batch_size=1"]);
+
+        let options = project.run_options(foundry).unwrap();
+        assert_eq!(options.mounts.len(), 1);
+        assert_eq!(options.mounts[0].host, "/scratch/weights");
+        assert_eq!(options.env["HYDRA_FULL_ERROR"], "0");
+        assert_eq!(options.env["TORCH_HOME"], "/torch");
+
+        let rosetta = App::Rosetta.spec();
+        assert_eq!(project.engine(rosetta), Some(ContainerEngine::Apptainer));
+        assert!(project.args(rosetta).is_empty());
+        let options = project.run_options(rosetta).unwrap();
+        assert!(options.mounts[0].host.ends_with("refs"));
+        assert!(options.mounts[0].read_only);
+    }
+
+    #[test]
+    fn options_given_on_the_command_line_are_not_repeated() {
+        let (_dir, project) = project(
+            r#"
+            args = ["-nstruct", "5", "-ignore_unrecognized_res", "-in:file:s", "a.pdb", "b.pdb"]
+
+            [apps.foundry]
+            args = ["diffusion_batch_size=1", "+seed=3", "--num_steps=50"]
+            "#,
+        );
+        let project = project.unwrap().unwrap();
+        let given = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            project.args_for(
+                App::Rosetta.spec(),
+                &given(&["score", "-nstruct", "1", "-in:file:s", "c.pdb"])
+            ),
+            ["-ignore_unrecognized_res"]
+        );
+        assert_eq!(
+            project.args_for(
+                App::Foundry.spec(),
+                &given(&["diffusion_batch_size=4", "seed=1", "--num_steps", "10"])
+            ),
+            [
+                "-nstruct",
+                "5",
+                "-ignore_unrecognized_res",
+                "-in:file:s",
+                "a.pdb",
+                "b.pdb"
+            ]
+        );
+        assert_eq!(
+            project.args_for(App::Rosetta.spec(), &given(&["score", "-1", "x=", "="])),
+            project.args(App::Rosetta.spec())
+        );
+    }
+
+    #[test]
+    fn unknown_app_is_rejected() {
+        let (_dir, project) = project("[apps.nosuchapp]\nargs = []\n");
+        assert!(project.unwrap_err().to_string().contains("unknown app"));
+    }
+
+    #[test]
+    fn unknown_field_is_rejected() {
+        let (_dir, project) = project("engines = \"docker\"\n");
+        assert!(project.is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use std::process::Stdio;
+
+use anyhow::{Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+use yansi::Paint;
+
+use crate::{
+    app::App,
+    config,
+    engine::{
+        ContainerEngine, hpc_image_path, hpc_images_root, pixi_env_is_current, pixi_evn_root,
+    },
+    util::{dirs, yansi::PaintExt},
+};
+
+/// Warn when the cache filesystem has less than this much space left, images are several GiB.
+const MIN_FREE_SPACE: u64 = 20 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    /// Not a problem, e.g. an image that will be pulled on first use
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    section: &'static str,
+    name: String,
+    status: Status,
+    detail: String,
+    /// Suggested command or action to resolve a warning or error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+#[derive(Default)]
+struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    fn add(
+        &mut self,
+        section: &'static str,
+        name: impl Into<String>,
+        status: Status,
+        detail: impl Into<String>,
+        fix: Option<String>,
+    ) {
+        self.checks.push(Check {
+            section,
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            fix,
+        });
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.checks.iter().filter(|c| c.status == status).count()
+    }
+}
+
+/// `rc doctor`: check engines, cache, working directory and installed apps, printing fixes.
+pub fn doctor(json: bool) -> Result<()> {
+    let mut report = Report::default();
+
+    let usable = check_engines(&mut report);
+    check_cache(&mut report);
+    check_working_dir(&mut report);
+    check_apps(&mut report, &usable);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report.checks)?);
+    } else {
+        print(&report);
+    }
+
+    match report.count(Status::Error) {
+        0 => Ok(()),
+        n => Err(anyhow!("rc doctor found {n} problem(s)")),
+    }
+}
+
+/// Check every engine, returns the ones usable on this host.
+fn check_engines(report: &mut Report) -> Vec<ContainerEngine> {
+    const SECTION: &str = "engines";
+    let mut usable = Vec::new();
+
+    for engine in ContainerEngine::iter().filter(|e| *e != ContainerEngine::Auto) {
+        let (binary, install) = match engine {
+            ContainerEngine::Docker => ("docker", "https://docs.docker.com/engine/install/"),
+            ContainerEngine::Podman => ("podman", "https://podman.io/docs/installation"),
+            ContainerEngine::Singularity => (
+                "singularity",
+                "https://docs.sylabs.io/guides/latest/user-guide/",
+            ),
+            ContainerEngine::Apptainer => ("apptainer", "https://apptainer.org/"),
+            ContainerEngine::None => ("pixi", "curl -fsSL https://pixi.sh/install.sh | sh"),
+            ContainerEngine::Auto => unreachable!(),
+        };
+        let name = format!("{engine} ({binary})");
+
+        if which::which(binary).is_err() {
+            report.add(
+                SECTION,
+                name,
+                Status::Info,
+                format!("`{binary}` not found on PATH"),
+                Some(format!("install it if you want to use it: {install}")),
+            );
+            continue;
+        }
+
+        let version = output(binary, &["--version"])
+            .ok()
+            .and_then(|v| v.lines().next().map(str::to_string))
+            .unwrap_or_else(|| "unknown version".into());
+
+        match engine {
+            ContainerEngine::Docker => match output("docker", &["info"]) {
+                Ok(_) => {
+                    report.add(
+                        SECTION,
+                        name,
+                        Status::Ok,
+                        format!("{version}, daemon reachable"),
+                        None,
+                    );
+                    usable.push(engine);
+                }
+                Err(e) if e.to_lowercase().contains("permission denied") => report.add(
+                    SECTION,
+                    name,
+                    Status::Warning,
+                    format!("{version}, current user may not access the Docker daemon"),
+                    Some("sudo usermod -aG docker $USER, then log out and back in".into()),
+                ),
+                Err(_) => report.add(
+                    SECTION,
+                    name,
+                    Status::Warning,
+                    format!("{version}, daemon not reachable"),
+                    Some(
+                        "start Docker, e.g. `sudo systemctl start docker` or Docker Desktop".into(),
+                    ),
+                ),
+            },
+            ContainerEngine::Singularity | ContainerEngine::Apptainer => {
+                let cache_var = format!("{}_CACHEDIR", binary.to_uppercase());
+                let pull_dirs: Vec<Utf8PathBuf> = std::iter::once(hpc_images_root())
+                    .chain(std::env::var(&cache_var).ok().map(Utf8PathBuf::from))
+                    .collect();
+
+                match pull_dirs.iter().find(|d| !is_writable(d)) {
+                    None => {
+                        report.add(
+                            SECTION,
+                            name,
+                            Status::Ok,
+                            format!("{version}, can pull images into {}", pull_dirs[0]),
+                            None,
+                        );
+                        usable.push(engine);
+                    }
+                    Some(dir) => report.add(
+                        SECTION,
+                        name,
+                        Status::Warning,
+                        format!("{version}, cannot pull images: {dir} is not writable"),
+                        Some(format!(
+                            "fix the permissions of {dir} or point `cache.root`/{cache_var} elsewhere"
+                        )),
+                    ),
+                }
+            }
+            _ => {
+                report.add(SECTION, name, Status::Ok, version, None);
+                usable.push(engine);
+            }
+        }
+    }
+
+    let settings = config::settings();
+    let configured = settings.engine();
+    if configured == ContainerEngine::Auto {
+        let priority = settings.engine_priority();
+        match priority.iter().find(|e| usable.contains(e)) {
+            Some(engine) => report.add(
+                SECTION,
+                "engine.default",
+                Status::Ok,
+                format!("auto, resolves to {engine}"),
+                None,
+            ),
+            None => report.add(
+                SECTION,
+                "engine.default",
+                Status::Error,
+                "auto, but no engine from `engine.priority` is usable",
+                Some("install Docker, Apptainer or Pixi (see above)".into()),
+            ),
+        }
+    } else if usable.contains(&configured) {
+        report.add(
+            SECTION,
+            "engine.default",
+            Status::Ok,
+            configured.to_string(),
+            None,
+        );
+    } else {
+        report.add(
+            SECTION,
+            "engine.default",
+            Status::Error,
+            format!("{configured} is configured but not usable"),
+            Some(format!(
+                "fix {configured} (see above) or `rc config set engine.default auto`"
+            )),
+        );
+    }
+
+    usable
+}
+
+fn check_cache(report: &mut Report) {
+    const SECTION: &str = "cache";
+    let root = dirs::cache_root();
+
+    if !is_writable(&root) {
+        report.add(
+            SECTION,
+            "cache root",
+            Status::Error,
+            format!("{root} is not writable"),
+            Some("fix its permissions or `rc config set cache.root <DIR>`".into()),
+        );
+        return;
+    }
+    report.add(
+        SECTION,
+        "cache root",
+        Status::Ok,
+        format!("{root} is writable"),
+        None,
+    );
+
+    if let Some(free) = free_space(&root) {
+        let detail = format!("{} free", crate::cache::format_size(free));
+        if free < MIN_FREE_SPACE {
+            report.add(
+                SECTION,
+                "disk space",
+                Status::Warning,
+                detail,
+                Some("free up space, e.g. `rc cache prune --stale` or `rc cache prune --older-than 30d`".into()),
+            );
+        } else {
+            report.add(SECTION, "disk space", Status::Ok, detail, None);
+        }
+    }
+}
+
+fn check_working_dir(report: &mut Report) {
+    const SECTION: &str = "working directory";
+    match std::env::current_dir().map(Utf8PathBuf::try_from) {
+        Ok(Ok(dir)) => report.add(SECTION, "path", Status::Ok, dir.to_string(), None),
+        Ok(Err(e)) => report.add(
+            SECTION,
+            "path",
+            Status::Error,
+            format!("{} is not valid UTF-8", e.as_path().display()),
+            Some("run rc from (or pass `-w`) a directory whose path is valid UTF-8".into()),
+        ),
+        Err(e) => report.add(
+            SECTION,
+            "path",
+            Status::Error,
+            format!("current directory is not accessible: {e}"),
+            None,
+        ),
+    }
+}
+
+fn check_apps(report: &mut Report, usable: &[ContainerEngine]) {
+    const SECTION: &str = "apps";
+    let settings = config::settings();
+
+    for app in App::iter() {
+        let spec = app.spec();
+        let image = settings.container_image(spec);
+
+        for engine in usable {
+            let name = format!("{app} ({engine})");
+            let install = Some(format!("rc install {app} -e {engine}"));
+            match engine {
+                ContainerEngine::Docker | ContainerEngine::Podman => {
+                    if engine.engine().is_installed(spec) {
+                        report.add(SECTION, name, Status::Ok, image.clone(), None);
+                    } else {
+                        report.add(
+                            SECTION,
+                            name,
+                            Status::Info,
+                            format!("{image} not pulled yet, it will be on first run"),
+                            install,
+                        );
+                    }
+                }
+                ContainerEngine::Singularity | ContainerEngine::Apptainer => {
+                    let path = hpc_image_path(&image);
+                    if path.exists() {
+                        report.add(SECTION, name, Status::Ok, path.to_string(), None);
+                    } else {
+                        report.add(
+                            SECTION,
+                            name,
+                            Status::Info,
+                            format!("{path} not built yet, it will be on first run"),
+                            install,
+                        );
+                    }
+                }
+                ContainerEngine::None if spec.pixi_recipe().is_some() => {
+                    let root = pixi_evn_root(spec);
+                    if !root.exists() {
+                        report.add(
+                            SECTION,
+                            name,
+                            Status::Info,
+                            "environment not set up yet, it will be on first run",
+                            install,
+                        );
+                    } else if pixi_env_is_current(spec) {
+                        report.add(SECTION, name, Status::Ok, root.to_string(), None);
+                    } else {
+                        report.add(
+                            SECTION,
+                            name,
+                            Status::Warning,
+                            format!("{root} was built from an older recipe"),
+                            install,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn print(report: &Report) {
+    let mut section = "";
+    for check in &report.checks {
+        if check.section != section {
+            section = check.section;
+            println!("\n{}", section.bold().underline());
+        }
+
+        let mark = match check.status {
+            Status::Ok => "✓".green(),
+            Status::Info => "-".dim(),
+            Status::Warning => "!".orange(),
+            Status::Error => "✗".bright_red(),
+        };
+        println!("  {mark} {:<32} {}", check.name, check.detail);
+        if let Some(fix) = &check.fix
+            && check.status != Status::Ok
+        {
+            println!("      {} {}", "→".dim(), fix.dim());
+        }
+    }
+
+    println!(
+        "\n{} warning(s), {} error(s)",
+        report.count(Status::Warning),
+        report.count(Status::Error)
+    );
+}
+
+/// Stdout of `program args` if it exits successfully, its stderr otherwise.
+fn output(program: &str, args: &[&str]) -> Result<String, String> {
+    match std::process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+    {
+        Ok(o) if o.status.success() => Ok(String::from_utf8_lossy(&o.stdout).into()),
+        Ok(o) => Err(String::from_utf8_lossy(&o.stderr).into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Whether files can be created in `dir`, creating it if needed.
+fn is_writable(dir: &Utf8Path) -> bool {
+    let probe = dir.join(format!(".rc-doctor-{}", std::process::id()));
+    std::fs::create_dir_all(dir).is_ok()
+        && std::fs::write(&probe, "").is_ok()
+        && std::fs::remove_file(&probe).is_ok()
+}
+
+#[cfg(unix)]
+fn free_space(dir: &Utf8Path) -> Option<u64> {
+    let stat = nix::sys::statvfs::statvfs(dir.as_std_path()).ok()?;
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_dir: &Utf8Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writable_dirs_are_detected() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+        assert!(is_writable(&dir.join("nested")));
+        assert!(dir.join("nested").read_dir().unwrap().next().is_none());
+    }
+
+    #[test]
+    fn report_counts_statuses() {
+        let mut report = Report::default();
+        report.add("s", "a", Status::Ok, "", None);
+        report.add("s", "b", Status::Warning, "", None);
+        report.add("s", "c", Status::Error, "", Some("fix".into()));
+        assert_eq!(report.count(Status::Warning), 1);
+        assert_eq!(report.count(Status::Error), 1);
+        assert_eq!(report.count(Status::Info), 0);
+    }
+}
@@ -1,20 +1,78 @@
-use anyhow::Result;
-use camino::Utf8PathBuf;
+use std::collections::BTreeMap;
+
+use anyhow::{Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::ValueEnum;
 use strum::IntoEnumIterator;
 use yansi::Paint;
 
 use crate::{
     app::{App, AppSpec},
-    engine::ContainerEngine,
+    config::{self, Project},
+    engine::{ContainerEngine, Gpus, Mount, RunOptions, auto_mount, gpu_available, pixi_evn_root},
+    image,
+    telemetry::{Manifest, Telemetry, is_secret, secret_hash},
     util::yansi::PaintExt,
 };
 
+/// Run `app`, `options` holds the command line settings, project file settings are added to them.
 pub fn run(
     app: &dyn AppSpec,
-    args: Vec<String>,
-    engine: ContainerEngine,
+    mut args: Vec<String>,
+    engine: Option<ContainerEngine>,
+    mut options: RunOptions,
     working_dir: Utf8PathBuf,
 ) -> Result<()> {
+    let project = Project::discover(&working_dir)?;
+
+    let engine = engine
+        .unwrap_or_else(|| config::settings().engine_for(app, project.as_ref()))
+        .resolve(app)?;
+
+    options.user_args = args.clone();
+    if let Some(project) = &project {
+        println!("Using project file: {}", project.path.bright_blue());
+        args.extend(project.args_for(app, &options.user_args));
+        let project_options = project.run_options(app)?;
+        // command line mounts take precedence over project ones for the same container path
+        let mut mounts = project_options.mounts;
+        mounts.retain(|m| !options.mounts.iter().any(|o| o.container == m.container));
+        // checked here rather than when the file is loaded, so `rc config show` works without them
+        if let Some(m) = mounts.iter().find(|m| !m.host.exists()) {
+            return Err(anyhow!(
+                "mount source {} from project file {} does not exist",
+                m.host,
+                project.path
+            ));
+        }
+        mounts.append(&mut options.mounts);
+        options.mounts = mounts;
+        // as are command line environment variables
+        let mut env = project_options.env;
+        env.append(&mut options.env);
+        options.env = env;
+    }
+
+    options.gpus = gpus(app, options.gpus.take());
+
+    if engine != ContainerEngine::None {
+        options.image = Some(image::run_image(
+            app,
+            options.image.as_deref(),
+            &working_dir,
+        )?);
+
+        if !options.no_auto_mount {
+            let auto = auto_mount(args, &working_dir, &options.mounts);
+            for r in &auto.rewrites {
+                println!("Rewrote argument {} -> {}", r.from, r.to.bright_blue());
+            }
+            args = auto.args;
+            options.auto_mounts = auto.mounts;
+            options.rewrites = auto.rewrites;
+        }
+    }
+
     println!(
         "Running app: {} in directory: {}{}",
         app.name().green(),
@@ -26,13 +84,248 @@ pub fn run(
         }
     );
 
-    engine.engine().execute(app, args, &working_dir)
+    engine.engine().execute(app, args, &working_dir, &options)
+}
+
+/// GPUs for a run of `app`: the `--gpus` selection when given, otherwise every GPU for apps that
+/// want one. Warns when such an app would run without a GPU.
+fn gpus(app: &dyn AppSpec, requested: Option<Gpus>) -> Option<Gpus> {
+    let warn = |reason: &str| {
+        eprintln!(
+            "{}",
+            format!(
+                "{} is a GPU app but {reason}, it will be slow or fail on the CPU",
+                app.name()
+            )
+            .orange()
+        );
+    };
+
+    match requested {
+        Some(Gpus::None) if app.wants_gpu() => {
+            warn("`--gpus none` was given");
+            Some(Gpus::None)
+        }
+        Some(gpus) => Some(gpus),
+        None if !app.wants_gpu() => None,
+        None if gpu_available() => Some(Gpus::All),
+        None => {
+            warn("no GPU was detected (pass `--gpus` to request one anyway)");
+            None
+        }
+    }
+}
+
+/// Re-execute a previous run recorded in `working_dir`, on its original engine unless `engine`
+/// is given.
+pub fn rerun(
+    reference: &str,
+    engine: Option<ContainerEngine>,
+    working_dir: Utf8PathBuf,
+) -> Result<()> {
+    let t = Telemetry::find(&working_dir, reference)?;
+    let manifest = t.read_manifest()?;
+
+    let app = App::iter()
+        .find(|a| a.spec().name() == manifest.app)
+        .ok_or_else(|| anyhow!("run {} used unknown app `{}`", t.id(), manifest.app))?;
+    let recorded_engine = ContainerEngine::from_str(&manifest.engine, true)
+        .map_err(|_| anyhow!("run {} used unknown engine `{}`", t.id(), manifest.engine))?;
+    let engine = engine.unwrap_or(recorded_engine).resolve(app.spec())?;
+
+    println!(
+        "Re-running run {} of {} from {}",
+        t.id(),
+        app.green(),
+        manifest.started.bright_blue()
+    );
+    warn_if_changed(app.spec(), engine, &manifest, &manifest.working_dir);
+
+    let options = recorded_options(&manifest, engine)?;
+    run(
+        app.spec(),
+        manifest.user_args,
+        Some(engine),
+        options,
+        manifest.working_dir,
+    )
+}
+
+/// Run options of a recorded run, to run it again on `engine`. The image is only kept for
+/// container engines.
+fn recorded_options(manifest: &Manifest, engine: ContainerEngine) -> Result<RunOptions> {
+    let mounts = manifest
+        .user_mounts
+        .iter()
+        .map(|m| Mount::parse(m, &manifest.working_dir))
+        .collect::<Result<_>>()?;
+    let gpus = manifest.gpus.as_deref().map(str::parse).transpose()?;
+
+    Ok(RunOptions {
+        env: recorded_env(manifest)?,
+        tags: manifest.tags.clone(),
+        note: manifest.note.clone(),
+        image: manifest
+            .image
+            .clone()
+            .filter(|_| engine != ContainerEngine::None),
+        mounts,
+        gpus,
+        limits: manifest.limits.clone(),
+        no_auto_mount: manifest.no_auto_mount,
+        ..Default::default()
+    })
 }
 
-pub fn install(app: App, engine: ContainerEngine) -> Result<()> {
+/// Environment of a recorded run. Secrets were only recorded as hashes, their values are taken
+/// from the current environment.
+fn recorded_env(manifest: &Manifest) -> Result<BTreeMap<String, String>> {
+    manifest
+        .env
+        .iter()
+        .map(|(key, value)| {
+            if !(is_secret(key) && value.starts_with("blake3:")) {
+                return Ok((key.clone(), value.clone()));
+            }
+            let current = std::env::var(key).map_err(|_| {
+                anyhow!("the run used the secret {key}, set it in the environment to run it again")
+            })?;
+            if secret_hash(&current) != *value {
+                eprintln!(
+                    "{}",
+                    format!("{key} differs from the value the run used, results may differ")
+                        .orange()
+                );
+            }
+            Ok((key.clone(), current))
+        })
+        .collect()
+}
+
+/// Warn when the image digest or Pixi recipe `engine` would use now differs from the recorded one.
+fn warn_if_changed(
+    app: &dyn AppSpec,
+    engine: ContainerEngine,
+    manifest: &Manifest,
+    working_dir: &Utf8Path,
+) {
+    let (what, recorded, current) = match engine {
+        ContainerEngine::None => (
+            "Pixi recipe",
+            manifest.pixi_recipe_hash.clone(),
+            app.pixi_recipe()
+                .map(|r| blake3::hash(r.as_bytes()).to_hex().to_string()),
+        ),
+        _ => (
+            "image digest",
+            manifest.digest.clone(),
+            image::current_digest(app, engine, working_dir),
+        ),
+    };
+
+    let message = match (recorded, current) {
+        (Some(recorded), Some(current)) if recorded != current => format!(
+            "The {what} changed since this run (recorded {recorded}, now {current}), results may differ"
+        ),
+        (Some(recorded), None) => format!(
+            "Could not verify that the {what} is unchanged since this run (recorded {recorded})"
+        ),
+        _ => return,
+    };
+    eprintln!("{}", message.orange());
+}
+
+pub fn install(app: App, engine: ContainerEngine, shared: bool) -> Result<()> {
     println!("Install app: {}", app.bright_green());
 
-    engine.engine().install(app.spec())
+    let engine = engine.resolve(app.spec())?;
+    engine.engine().install(app.spec(), shared)
+}
+
+/// Refresh the images or native environments of `app`, every app when `None`, and print which of
+/// them changed version.
+pub fn update(app: Option<App>, container_engine: Option<ContainerEngine>) -> Result<()> {
+    let every_app = app.is_none();
+    let apps = match app {
+        Some(app) => vec![app],
+        None => App::iter().collect(),
+    };
+
+    let mut summary = Vec::new();
+    for app in apps {
+        let engine = container_engine.unwrap_or_else(|| config::settings().engine());
+        let engine = match engine.resolve(app.spec()) {
+            Ok(engine) => engine,
+            Err(e) => {
+                summary.push((app, engine, Err(e)));
+                continue;
+            }
+        };
+        if engine == ContainerEngine::None && app.spec().pixi_recipe().is_none() {
+            continue;
+        }
+        // updating every app only refreshes what is installed, it never downloads new images
+        if every_app && !is_present(app.spec(), engine) {
+            continue;
+        }
+        println!(
+            "{}",
+            format!("Updating {app} for {engine}...").bright_blue()
+        );
+        summary.push((app, engine, engine.engine().update(app.spec())));
+    }
+
+    println!("\n{}", "Update summary:".bold());
+    let mut failed = 0;
+    for (app, engine, update) in &summary {
+        let name = format!("{app} ({engine})");
+        match update {
+            Ok(update) => {
+                let status = match (&update.before, &update.after) {
+                    (None, Some(_)) => "installed".green(),
+                    _ if update.changed() => "updated".green(),
+                    _ => "unchanged".dim(),
+                };
+                let versions = match (&update.before, &update.after) {
+                    (Some(before), Some(after)) if update.changed() => {
+                        format!("{} -> {}", short_version(before), short_version(after))
+                    }
+                    (_, Some(after)) => short_version(after),
+                    _ => "-".into(),
+                };
+                println!("  {:<32} {:<44} {status}", name.green(), versions);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("  {:<32} {}", name.green(), format!("failed: {e:#}").red());
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        n => Err(anyhow!("{n} of {} updates failed", summary.len())),
+    }
+}
+
+/// Whether `app` has an image or environment for `engine` at all, current or not.
+fn is_present(app: &dyn AppSpec, engine: ContainerEngine) -> bool {
+    match engine {
+        ContainerEngine::None if config::settings().native_prefix(app).is_none() => {
+            pixi_evn_root(app).is_dir()
+        }
+        engine => engine.engine().is_installed(app),
+    }
+}
+
+/// Digest or signature shortened for display, e.g. `sha256:0123456789ab`.
+fn short_version(version: &str) -> String {
+    let (prefix, hash) = version.split_once(':').unwrap_or(("", version));
+    let hash = &hash[..hash.len().min(12)];
+    match prefix {
+        "" => hash.into(),
+        prefix => format!("{prefix}:{hash}"),
+    }
 }
 
 pub fn clean(app: Option<App>, container_engine: Option<ContainerEngine>) -> Result<()> {
@@ -46,12 +339,17 @@ pub fn clean(app: Option<App>, container_engine: Option<ContainerEngine>) -> Res
     } else {
         ContainerEngine::iter()
             // when only need a single HPC engine
-            .filter(|a| a != &ContainerEngine::Apptainer)
+            .filter(|a| a != &ContainerEngine::Apptainer && a != &ContainerEngine::Auto)
+            // images of Docker and Podman live in their own stores, only reachable with the tool
+            .filter(|a| {
+                !matches!(a, ContainerEngine::Docker | ContainerEngine::Podman) || a.is_usable()
+            })
             .collect()
     };
 
     for app in apps {
         for engine in &engines {
+            let engine = engine.resolve(app)?;
             println!(
                 "{}",
                 format!("Cleaning {engine} engine data for {:?} app...", app.name()).orange()
@@ -1,38 +1,696 @@
+mod auto_mount;
 mod docker;
 mod hpc;
 mod native;
+mod podman;
 
-use anyhow::Result;
-use camino::Utf8Path;
+use std::{collections::BTreeMap, fmt, fs, process::Stdio, str::FromStr};
+
+use anyhow::{Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use yansi::Paint;
 
-use crate::app::AppSpec;
+use crate::{
+    app::{AppSpec, ContainerConfig, MountRole, NativeRunSpec},
+    config,
+    telemetry::Telemetry,
+};
 
-pub use hpc::hpc_image_path;
-pub use native::pixi_evn_root;
+pub use auto_mount::{Rewrite, auto_mount};
+pub use hpc::{cached_image_path, digest_file, hpc_image_path, hpc_images_root};
+pub use native::{cached_pixi_evn_root, pixi_env_is_current, pixi_envs_root, pixi_evn_root};
 
-#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, strum::Display, strum::EnumIter)]
+#[derive(
+    ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumIter,
+)]
 #[strum(serialize_all = "lowercase")] //  "kebab-case"
+#[serde(rename_all = "lowercase")]
 pub enum ContainerEngine {
     Docker,
+    /// Rootless Podman, the container runs with the invoking user's id
+    Podman,
     Singularity,
     Apptainer,
     None,
+    /// Pick the first usable engine from `engine.priority`
+    Auto,
+}
+
+/// Bind mount requested by the user rather than by the app spec, written as `host[:container][:ro]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub host: Utf8PathBuf,
+    pub container: String,
+    pub read_only: bool,
+}
+
+/// GPUs made available to a run, written as `all`, `none`, a count `N` or a device list
+/// `device=0,2` (a bare `0,2` also works).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Gpus {
+    None,
+    All,
+    Count(u32),
+    Devices(Vec<String>),
+}
+
+/// Resource limits for a run, mapped to engine flags or, for native runs, thread count variables.
+#[derive(clap::Args, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Number of CPUs the run may use, e.g. `4` or `1.5`
+    #[arg(long, value_parser = Limits::parse_cpus)]
+    pub cpus: Option<f64>,
+
+    /// Memory limit, e.g. `16g` or `512m`
+    #[arg(long, value_parser = Limits::parse_size)]
+    pub memory: Option<String>,
+
+    /// Size of `/dev/shm` in the container, e.g. `8g` (PyTorch data loaders need more than 64m)
+    #[arg(long, value_parser = Limits::parse_size)]
+    pub shm_size: Option<String>,
+}
+
+/// Variables capping the thread pools of OpenMP and the common BLAS libraries.
+pub const THREAD_ENV_VARS: [&str; 4] = [
+    "OMP_NUM_THREADS",
+    "MKL_NUM_THREADS",
+    "OPENBLAS_NUM_THREADS",
+    "NUMEXPR_NUM_THREADS",
+];
+
+/// User supplied run settings (project file, command line) applied on top of the app spec.
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions {
+    /// Arguments exactly as given on the command line, recorded in the run manifest
+    pub user_args: Vec<String>,
+    /// Labels and free-form note attached to the run with `--tag`/`--note`
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    /// Container image for this run, `None` means the configured image of the app
+    pub image: Option<String>,
+    pub mounts: Vec<Mount>,
+    /// Environment variables from the project file, `--env-file` and `--env`
+    pub env: BTreeMap<String, String>,
+    /// GPUs for the run, `None` until the driver applies the app's default
+    pub gpus: Option<Gpus>,
+    pub limits: Limits,
+    /// Disable rewriting host paths in the arguments, see [`auto_mount`]
+    pub no_auto_mount: bool,
+    /// Arguments rewritten by [`auto_mount`], shown in the run log
+    pub rewrites: Vec<Rewrite>,
+    /// Read-only mounts added by [`auto_mount`] for the rewritten arguments
+    pub auto_mounts: Vec<Mount>,
+    /// Run id reserved by `rc submit`, the run records its log under the id of the batch script
+    pub run_id: Option<u32>,
 }
 
 pub trait Engine {
-    fn execute(&self, app: &dyn AppSpec, args: Vec<String>, working_dir: &Utf8Path) -> Result<()>;
-    fn install(&self, app: &dyn AppSpec) -> Result<()>;
+    fn execute(
+        &self,
+        app: &dyn AppSpec,
+        args: Vec<String>,
+        working_dir: &Utf8Path,
+        options: &RunOptions,
+    ) -> Result<()>;
+    /// Set up `app` ahead of its first run, into the site-wide shared root when `shared`.
+    fn install(&self, app: &dyn AppSpec, shared: bool) -> Result<()>;
     fn clean(&self, app: &dyn AppSpec) -> Result<()>;
+    /// Make the image or environment a run of `app` with `options` uses available ahead of the
+    /// run, e.g. before submitting batch jobs that would otherwise all build it at once. Engines
+    /// whose images live in a per-host store leave it to the run.
+    fn prepare(&self, _app: &dyn AppSpec, _options: &RunOptions) -> Result<()> {
+        Ok(())
+    }
+    /// Whether the image or environment of `app` is already present, so a run needs no setup.
+    fn is_installed(&self, app: &dyn AppSpec) -> bool;
+    /// Refresh the image or environment of `app` from upstream, installing it when missing.
+    fn update(&self, app: &dyn AppSpec) -> Result<Update>;
+}
+
+/// Version of an app's image (its registry digest) or native environment (its recipe signature)
+/// before and after `rc update`, `None` when it was not installed or, for images without a
+/// registry digest, is unknown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl Update {
+    pub fn changed(&self) -> bool {
+        self.before != self.after
+    }
 }
 
 impl ContainerEngine {
     pub fn engine(self) -> &'static dyn Engine {
         match self {
             ContainerEngine::Docker => &docker::DOCKER,
+            ContainerEngine::Podman => &podman::PODMAN,
             ContainerEngine::Singularity => &hpc::SINGULARITY,
             ContainerEngine::Apptainer => &hpc::APPTAINER,
             ContainerEngine::None => &native::NATIVE,
+            ContainerEngine::Auto => panic!("`auto` engine must be resolved before use"),
+        }
+    }
+
+    /// Resolve `auto` into the first engine from `engine.priority` that is usable on this host
+    /// and supported by `app`, concrete engines are returned unchanged.
+    pub fn resolve(self, app: &dyn AppSpec) -> Result<ContainerEngine> {
+        if self != ContainerEngine::Auto {
+            return Ok(self);
+        }
+
+        let priority = config::settings().engine_priority();
+        // an app with an existing native installation does not need Pixi
+        let native_prefix = config::settings().native_prefix(app).is_some();
+        let usable =
+            |e: ContainerEngine| e.is_usable() || (e == ContainerEngine::None && native_prefix);
+        let engine = select(&priority, app, usable).ok_or_else(|| {
+            anyhow!(
+                "Could not find a usable container engine for {} (tried: {}), install one of them or pass `-e`",
+                app.name(),
+                priority
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+
+        println!(
+            "{}",
+            format!("Automatically selected container engine: {engine}").bright_blue()
+        );
+        Ok(engine)
+    }
+
+    /// Check whether the engine can be used on this host: the binary is on PATH and, for Docker,
+    /// the daemon is reachable by the current user.
+    pub fn is_usable(self) -> bool {
+        let binary = match self {
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+            ContainerEngine::Singularity => "singularity",
+            ContainerEngine::Apptainer => "apptainer",
+            ContainerEngine::None => "pixi",
+            ContainerEngine::Auto => return false,
+        };
+
+        if which::which(binary).is_err() {
+            return false;
+        }
+
+        match self {
+            ContainerEngine::Docker => std::process::Command::new("docker")
+                .arg("info")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success()),
+            _ => true,
+        }
+    }
+}
+
+impl RunOptions {
+    /// Container spec of `app` with the user and auto mounts added as `Input`/`Data` mounts and
+    /// the user environment merged over the app's.
+    pub fn container_spec(&self, app: &dyn AppSpec, args: Vec<String>) -> ContainerConfig {
+        let mut spec = self.mounts.iter().chain(&self.auto_mounts).fold(
+            app.container_spec(args),
+            |spec, mount| match mount.role() {
+                MountRole::Input(host) => spec.input(host, &mount.container),
+                MountRole::Data(host) => spec.data(host, &mount.container),
+                _ => unreachable!(),
+            },
+        );
+        spec.env.extend(self.env.clone());
+        spec
+    }
+
+    /// Native spec of `app` with the user environment merged over the app's.
+    pub fn native_spec(
+        &self,
+        app: &dyn AppSpec,
+        args: Vec<String>,
+        working_dir: &Utf8Path,
+    ) -> NativeRunSpec {
+        let mut spec = app.native_spec(args, working_dir);
+        spec.env.extend(self.env.clone());
+        spec
+    }
+
+    /// Lines describing the argument rewrites, written to the run log after the command line.
+    pub fn rewrite_log(&self) -> String {
+        self.rewrites
+            .iter()
+            .map(|r| format!("# rewrote argument {} -> {}\n", r.from, r.to))
+            .collect()
+    }
+}
+
+/// Bind mounts `host:container[:ro]` of a container run: the working directory at `/w` first,
+/// then the mounts of `spec` in order. The scratch directory is created when requested.
+fn container_binds(
+    spec: &ContainerConfig,
+    work_dir: &Utf8Path,
+    t: &Telemetry,
+) -> Result<Vec<String>> {
+    let mut binds = vec![format!("{work_dir}:/w")];
+    for (role, container) in &spec.mounts {
+        match role {
+            MountRole::WorkingDir => {}
+            MountRole::Scratch => {
+                let d = t.scratch_dir();
+                fs::create_dir_all(&d)?;
+                binds.push(format!("{d}:/{container}"));
+            }
+            MountRole::Input(host) => binds.push(format!("{host}:{container}:ro")),
+            MountRole::Data(host) => binds.push(format!("{host}:{container}")),
+        }
+    }
+    Ok(binds)
+}
+
+/// First engine in `priority` that supports `app` and passes `is_usable`.
+fn select(
+    priority: &[ContainerEngine],
+    app: &dyn AppSpec,
+    is_usable: impl Fn(ContainerEngine) -> bool,
+) -> Option<ContainerEngine> {
+    priority
+        .iter()
+        .copied()
+        .filter(|e| *e != ContainerEngine::Auto)
+        .filter(|e| *e != ContainerEngine::None || app.pixi_recipe().is_some())
+        .find(|e| is_usable(*e))
+}
+
+/// Parse an environment variable `KEY=VALUE`, a bare `KEY` takes its value from the current
+/// environment.
+pub fn parse_env_var(s: &str) -> Result<(String, String)> {
+    let (key, value) = match s.split_once('=') {
+        Some((key, value)) => (key, value.to_string()),
+        None => (
+            s,
+            std::env::var(s).map_err(|_| anyhow!("environment variable `{s}` is not set"))?,
+        ),
+    };
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(anyhow!(
+            "invalid environment variable `{s}`, expected KEY=VALUE"
+        ));
+    }
+    Ok((key.into(), value))
+}
+
+/// Read a Docker style env file: one `KEY=VALUE` (or bare `KEY`) per line, `#` starts a comment.
+pub fn read_env_file(path: &Utf8Path) -> Result<BTreeMap<String, String>> {
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow!("failed to read env file {path}: {e}"))?;
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            parse_env_var(line).map_err(|e| anyhow!("{path}:{}: {e}", i + 1))
+        })
+        .collect()
+}
+
+/// Whether an NVIDIA GPU is usable on this host, judged by `nvidia-smi -L` listing one.
+pub fn gpu_available() -> bool {
+    std::process::Command::new("nvidia-smi")
+        .arg("-L")
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|o| o.status.success() && !o.stdout.trim_ascii().is_empty())
+}
+
+impl Gpus {
+    /// Value for `CUDA_VISIBLE_DEVICES`, `None` when every GPU should stay visible.
+    pub fn visible_devices(&self) -> Option<String> {
+        match self {
+            Gpus::None => Some(String::new()),
+            Gpus::All => None,
+            Gpus::Count(n) => Some((0..*n).map(|i| i.to_string()).collect::<Vec<_>>().join(",")),
+            Gpus::Devices(devices) => Some(devices.join(",")),
+        }
+    }
+
+    /// Device ids to expose, `None` for all GPUs and an empty list for no GPU.
+    pub fn device_ids(&self) -> Option<Vec<String>> {
+        match self {
+            Gpus::All => None,
+            gpus => Some(
+                gpus.visible_devices()
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter(|d| !d.is_empty())
+                    .map(String::from)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl FromStr for Gpus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let devices = |list: &str| -> Result<Gpus> {
+            let devices: Vec<String> = list.split(',').map(|d| d.trim().to_string()).collect();
+            if devices.iter().any(String::is_empty) {
+                return Err(anyhow!("invalid GPU device list `{s}`"));
+            }
+            Ok(Gpus::Devices(devices))
+        };
+
+        match s {
+            "all" => Ok(Gpus::All),
+            "none" | "0" => Ok(Gpus::None),
+            _ => match (s.strip_prefix("device="), s.parse::<u32>()) {
+                (Some(list), _) => devices(list),
+                (None, Ok(n)) => Ok(Gpus::Count(n)),
+                (None, Err(_)) if s.contains(',') => devices(s),
+                _ => Err(anyhow!(
+                    "invalid GPU selection `{s}`, expected all, none, a count or device=<id>[,<id>...]"
+                )),
+            },
         }
     }
 }
+
+impl fmt::Display for Gpus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gpus::None => write!(f, "none"),
+            Gpus::All => write!(f, "all"),
+            Gpus::Count(n) => write!(f, "{n}"),
+            Gpus::Devices(devices) => write!(f, "device={}", devices.join(",")),
+        }
+    }
+}
+
+impl Limits {
+    /// Threads for `THREAD_ENV_VARS`, `cpus` rounded up.
+    pub fn threads(&self) -> Option<u32> {
+        self.cpus.map(|cpus| cpus.ceil() as u32)
+    }
+
+    fn parse_cpus(s: &str) -> Result<f64> {
+        match s.parse::<f64>() {
+            Ok(cpus) if cpus.is_finite() && cpus > 0.0 => Ok(cpus),
+            _ => Err(anyhow!("expected a positive number of CPUs")),
+        }
+    }
+
+    /// Accept sizes as understood by `docker run`: a number with an optional `b`, `k`, `m`, `g`
+    /// or `t` unit, e.g. `512m` or `16GB`.
+    fn parse_size(s: &str) -> Result<String> {
+        let lower = s.to_lowercase();
+        let number = lower
+            .trim_end_matches("ib")
+            .trim_end_matches('b')
+            .trim_end_matches(['k', 'm', 'g', 't']);
+        match number.parse::<f64>() {
+            Ok(n) if n.is_finite() && n > 0.0 => Ok(s.into()),
+            _ => Err(anyhow!("invalid size `{s}`, expected e.g. `512m` or `16g`")),
+        }
+    }
+}
+
+/// Written back as `host:container[:ro]`, as accepted by [`Mount::parse`].
+impl fmt::Display for Mount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.host, self.container)?;
+        if self.read_only {
+            write!(f, ":ro")?;
+        }
+        Ok(())
+    }
+}
+
+impl Mount {
+    /// Parse `host[:container][:ro|:rw]`, relative host paths are resolved against `base`.
+    /// When the container path is omitted the host path is mounted at the same location.
+    pub fn parse(spec: &str, base: &Utf8Path) -> Result<Self> {
+        let mut parts: Vec<&str> = spec.split(':').collect();
+
+        let read_only = match parts.last() {
+            Some(&"ro") if parts.len() > 1 => {
+                parts.pop();
+                true
+            }
+            Some(&"rw") if parts.len() > 1 => {
+                parts.pop();
+                false
+            }
+            _ => false,
+        };
+
+        let (host, container) = match parts.as_slice() {
+            [host] if !host.is_empty() => (*host, None),
+            [host, container] if !host.is_empty() && !container.is_empty() => {
+                (*host, Some(*container))
+            }
+            _ => {
+                return Err(anyhow!(
+                    "invalid mount `{spec}`, expected host[:container][:ro]"
+                ));
+            }
+        };
+
+        let host = base.join(host);
+        let container = container
+            .map(String::from)
+            .unwrap_or_else(|| host.to_string());
+
+        if !container.starts_with('/') {
+            return Err(anyhow!(
+                "invalid mount `{spec}`, container path must be absolute"
+            ));
+        }
+        if container.trim_end_matches('/') == "/w" {
+            return Err(anyhow!(
+                "invalid mount `{spec}`, /w is where the working directory is mounted"
+            ));
+        }
+
+        Ok(Mount {
+            host,
+            container,
+            read_only,
+        })
+    }
+
+    /// Read-only mounts provide inputs, writable ones data.
+    pub fn role(&self) -> MountRole {
+        match self.read_only {
+            true => MountRole::Input(self.host.clone()),
+            false => MountRole::Data(self.host.clone()),
+        }
+    }
+
+    /// `host:container[:ro]` as understood by both `docker --volume` and `apptainer --bind`.
+    pub fn bind_spec(&self) -> String {
+        format!(
+            "{}:{}{}",
+            self.host,
+            self.container,
+            if self.read_only { ":ro" } else { "" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+
+    #[test]
+    fn select_follows_priority() {
+        use ContainerEngine::*;
+        let app = App::Rosetta.spec();
+
+        assert_eq!(select(&[Docker, Apptainer], app, |_| true), Some(Docker));
+        assert_eq!(
+            select(&[Docker, Apptainer], app, |e| e == Apptainer),
+            Some(Apptainer)
+        );
+        assert_eq!(
+            select(&[Docker, Apptainer], app, |e| e == None),
+            Option::None
+        );
+    }
+
+    #[test]
+    fn select_skips_native_for_apps_without_pixi_recipe() {
+        use ContainerEngine::*;
+        assert_eq!(
+            select(&[None, Singularity], App::Colabfold.spec(), |_| true),
+            Some(Singularity)
+        );
+        assert_eq!(
+            select(&[None, Singularity], App::Rosetta.spec(), |_| true),
+            Some(None)
+        );
+    }
+
+    fn base() -> &'static Utf8Path {
+        Utf8Path::new("/project")
+    }
+
+    #[test]
+    fn mount_host_only() {
+        let m = Mount::parse("/data/shared", base()).unwrap();
+        assert_eq!(m.host, "/data/shared");
+        assert_eq!(m.container, "/data/shared");
+        assert!(!m.read_only);
+    }
+
+    #[test]
+    fn mount_relative_host_with_container_read_only() {
+        let m = Mount::parse("refs:/refs:ro", base()).unwrap();
+        assert_eq!(m.host, "/project/refs");
+        assert_eq!(m.container, "/refs");
+        assert!(m.read_only);
+        assert_eq!(m.bind_spec(), "/project/refs:/refs:ro");
+    }
+
+    #[test]
+    fn same_host_directory_at_two_container_paths() {
+        let options = RunOptions {
+            mounts: vec![
+                Mount::parse("/data:/a:ro", base()).unwrap(),
+                Mount::parse("/data:/b", base()).unwrap(),
+            ],
+            ..Default::default()
+        };
+        let spec = options.container_spec(App::Rosetta.spec(), vec![]);
+        let user: Vec<_> = spec
+            .mounts
+            .iter()
+            .filter(|(role, _)| matches!(role, MountRole::Input(_) | MountRole::Data(_)))
+            .collect();
+        assert_eq!(
+            user,
+            [
+                &(MountRole::Input("/data".into()), "/a".to_string()),
+                &(MountRole::Data("/data".into()), "/b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mount_host_read_only() {
+        let m = Mount::parse("/db:ro", base()).unwrap();
+        assert_eq!(m.container, "/db");
+        assert!(m.read_only);
+    }
+
+    #[test]
+    fn gpus_parse() {
+        assert_eq!("all".parse::<Gpus>().unwrap(), Gpus::All);
+        assert_eq!("none".parse::<Gpus>().unwrap(), Gpus::None);
+        assert_eq!("0".parse::<Gpus>().unwrap(), Gpus::None);
+        assert_eq!("2".parse::<Gpus>().unwrap(), Gpus::Count(2));
+        assert_eq!(
+            "device=1".parse::<Gpus>().unwrap(),
+            Gpus::Devices(vec!["1".into()])
+        );
+        assert_eq!(
+            "0,2".parse::<Gpus>().unwrap(),
+            Gpus::Devices(vec!["0".into(), "2".into()])
+        );
+        assert!("some".parse::<Gpus>().is_err());
+        assert!("device=1,".parse::<Gpus>().is_err());
+    }
+
+    #[test]
+    fn gpus_visible_devices() {
+        assert_eq!(Gpus::All.visible_devices(), Option::None);
+        assert_eq!(Gpus::None.visible_devices().as_deref(), Some(""));
+        assert_eq!(Gpus::Count(3).visible_devices().as_deref(), Some("0,1,2"));
+        assert_eq!(
+            Gpus::Count(2).device_ids(),
+            Some(vec!["0".into(), "1".into()])
+        );
+        assert_eq!(Gpus::None.device_ids(), Some(vec![]));
+        assert_eq!(
+            "device=0,2".parse::<Gpus>().unwrap().to_string(),
+            "device=0,2"
+        );
+    }
+
+    #[test]
+    fn limits_parse() {
+        assert_eq!(Limits::parse_cpus("1.5").unwrap(), 1.5);
+        assert!(Limits::parse_cpus("0").is_err());
+        assert!(Limits::parse_cpus("four").is_err());
+
+        for size in ["512m", "16g", "16GB", "2GiB", "1024", "1.5g"] {
+            assert_eq!(Limits::parse_size(size).unwrap(), size);
+        }
+        for size in ["", "g", "16x", "-1g"] {
+            assert!(Limits::parse_size(size).is_err(), "{size}");
+        }
+
+        let limits = Limits {
+            cpus: Some(2.5),
+            ..Default::default()
+        };
+        assert_eq!(limits.threads(), Some(3));
+    }
+
+    #[test]
+    fn env_vars() {
+        assert_eq!(
+            parse_env_var("HYDRA_FULL_ERROR=1").unwrap(),
+            ("HYDRA_FULL_ERROR".into(), "1".into())
+        );
+        assert_eq!(parse_env_var("A=b=c").unwrap().1, "b=c");
+        assert_eq!(parse_env_var("EMPTY=").unwrap().1, "");
+        assert!(parse_env_var("=1").is_err());
+        assert!(parse_env_var("RC_SURELY_UNSET_VARIABLE").is_err());
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = Utf8Path::from_path(dir.path()).unwrap().join("run.env");
+        fs::write(
+            &path,
+            "# torch\nTORCH_HOME=/models\n\nexport OMP_NUM_THREADS=4\n",
+        )
+        .unwrap();
+        let env = read_env_file(&path).unwrap();
+        assert_eq!(env["TORCH_HOME"], "/models");
+        assert_eq!(env["OMP_NUM_THREADS"], "4");
+
+        fs::write(&path, "OK=1\nnot valid\n").unwrap();
+        let err = read_env_file(&path).unwrap_err().to_string();
+        assert!(err.contains("run.env:2"), "{err}");
+    }
+
+    #[test]
+    fn mount_invalid() {
+        assert!(Mount::parse("", base()).is_err());
+        assert!(Mount::parse("/data:/w", base()).is_err());
+        assert!(Mount::parse("/data:/w/:ro", base()).is_err());
+        assert!(Mount::parse("/a:relative", base()).is_err());
+        assert!(Mount::parse("/a:/b:/c", base()).is_err());
+    }
+}
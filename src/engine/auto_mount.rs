@@ -0,0 +1,161 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Mount;
+
+/// Container directory under which the parents of argument paths are mounted.
+const AUTO_MOUNT_ROOT: &str = "/rc/mnt";
+
+/// An argument rewritten from a host path to the matching container path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rewrite {
+    pub from: String,
+    pub to: String,
+}
+
+/// Arguments with host paths rewritten, together with the read-only mounts they need.
+#[derive(Debug, Default)]
+pub struct AutoMounts {
+    pub args: Vec<String>,
+    pub mounts: Vec<Mount>,
+    pub rewrites: Vec<Rewrite>,
+}
+
+/// Find existing absolute host paths in `args`, either as a whole argument or as the value of
+/// `key=value`/`--opt=value`, and rewrite them to container paths. Paths inside `working_dir`
+/// map to `/w`, paths inside one of `mounts` to its container path, the parent directories of
+/// any others are mounted read-only under `/rc/mnt`.
+pub fn auto_mount(args: Vec<String>, working_dir: &Utf8Path, mounts: &[Mount]) -> AutoMounts {
+    let mut result = AutoMounts::default();
+
+    for arg in args {
+        let (prefix, value) = match arg.split_once('=') {
+            Some((key, value)) if value.starts_with('/') => (format!("{key}="), value),
+            _ => (String::new(), arg.as_str()),
+        };
+
+        let path = Utf8Path::new(value);
+        if !path.is_absolute() || path.parent().is_none() || !path.exists() {
+            result.args.push(arg);
+            continue;
+        }
+
+        let container = container_path(path, working_dir, mounts, &mut result.mounts);
+        let rewritten = format!("{prefix}{container}");
+        result.rewrites.push(Rewrite {
+            from: arg,
+            to: rewritten.clone(),
+        });
+        result.args.push(rewritten);
+    }
+
+    result
+}
+
+fn container_path(
+    path: &Utf8Path,
+    working_dir: &Utf8Path,
+    mounts: &[Mount],
+    auto_mounts: &mut Vec<Mount>,
+) -> String {
+    let inside = |host: &Utf8Path, container: &str| {
+        path.strip_prefix(host).ok().map(|rel| join(container, rel))
+    };
+
+    if let Some(p) = inside(working_dir, "/w") {
+        return p;
+    }
+    if let Some(p) = mounts
+        .iter()
+        .chain(auto_mounts.iter())
+        .find_map(|m| inside(&m.host, &m.container))
+    {
+        return p;
+    }
+
+    let host: Utf8PathBuf = match path.is_dir() {
+        true => path.into(),
+        false => path.parent().unwrap_or(path).into(),
+    };
+    let mount = Mount {
+        container: format!("{AUTO_MOUNT_ROOT}/{}", auto_mounts.len()),
+        host,
+        read_only: true,
+    };
+    let p = inside(&mount.host, &mount.container).unwrap_or_else(|| mount.container.clone());
+    auto_mounts.push(mount);
+    p
+}
+
+fn join(container: &str, rel: &Utf8Path) -> String {
+    match rel.as_str() {
+        "" => container.into(),
+        rel => format!("{}/{rel}", container.trim_end_matches('/')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn paths_are_rewritten_and_parents_mounted() {
+        let dir = TempDir::new().unwrap();
+        dir.child("work/in.pdb").write_str("ATOM").unwrap();
+        dir.child("shared/1abc.pdb").write_str("ATOM").unwrap();
+        dir.child("shared/2xyz.pdb").write_str("ATOM").unwrap();
+        dir.child("db/seqs").create_dir_all().unwrap();
+        dir.child("refs/ref.pdb").write_str("ATOM").unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+
+        let user_mounts = [Mount {
+            host: root.join("refs"),
+            container: "/refs".into(),
+            read_only: true,
+        }];
+
+        let args: Vec<String> = vec![
+            "--pdb_path".into(),
+            format!("{root}/shared/1abc.pdb"),
+            format!("inference.input_pdb={root}/shared/2xyz.pdb"),
+            format!("--db={root}/db/seqs"),
+            format!("{root}/work/in.pdb"),
+            format!("-ref={root}/refs/ref.pdb"),
+            format!("{root}/missing.pdb"),
+            "/".into(),
+            "contigs=[10-20]".into(),
+        ];
+
+        let result = auto_mount(args, &root.join("work"), &user_mounts);
+        assert_eq!(
+            result.args,
+            [
+                "--pdb_path".to_string(),
+                "/rc/mnt/0/1abc.pdb".into(),
+                "inference.input_pdb=/rc/mnt/0/2xyz.pdb".into(),
+                "--db=/rc/mnt/1".into(),
+                "/w/in.pdb".into(),
+                "-ref=/refs/ref.pdb".into(),
+                format!("{root}/missing.pdb"),
+                "/".into(),
+                "contigs=[10-20]".into(),
+            ]
+        );
+
+        assert_eq!(result.mounts.len(), 2);
+        assert_eq!(result.mounts[0].host, root.join("shared"));
+        assert_eq!(
+            result.mounts[0].bind_spec(),
+            format!("{root}/shared:/rc/mnt/0:ro")
+        );
+        assert_eq!(result.mounts[1].host, root.join("db/seqs"));
+
+        assert_eq!(result.rewrites.len(), 5);
+        assert_eq!(
+            result.rewrites[1].to,
+            "inference.input_pdb=/rc/mnt/0/2xyz.pdb"
+        );
+    }
+}
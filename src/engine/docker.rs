@@ -1,13 +1,15 @@
-use std::fs;
+use std::{fs, process::Stdio};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use camino::Utf8Path;
 use yansi::Paint;
 
 use crate::{
-    app::{AppSpec, MountRole},
-    engine::Engine,
-    telemetry::Telemetry,
+    app::AppSpec,
+    config,
+    engine::{Engine, Gpus, RunOptions, Update, container_binds},
+    image,
+    telemetry::{Manifest, Telemetry},
     util::{self},
 };
 
@@ -15,18 +17,35 @@ pub struct DockerEngine;
 pub static DOCKER: DockerEngine = DockerEngine;
 
 impl Engine for DockerEngine {
-    fn execute(&self, app: &dyn AppSpec, args: Vec<String>, work_dir: &Utf8Path) -> Result<()> {
-        let spec = app.container_spec(args);
+    fn execute(
+        &self,
+        app: &dyn AppSpec,
+        args: Vec<String>,
+        work_dir: &Utf8Path,
+        options: &RunOptions,
+    ) -> Result<()> {
+        let mut manifest = Manifest::new(app, "docker", &args, options, work_dir)?;
+        let spec = options.container_spec(app, args);
+        let image = options
+            .image
+            .clone()
+            .unwrap_or_else(|| config::settings().container_image(app));
+
+        pull(&image)?;
+        manifest.set_image(&image);
+        if manifest.digest.is_none() {
+            manifest.digest = image::local_digest("docker", &image);
+        }
 
-        self.install(app)?;
+        let t = Telemetry::for_run(work_dir, options.run_id)?;
 
-        let t = Telemetry::new(work_dir);
+        let mounts = container_binds(&spec, work_dir, &t)?;
 
         let mut cmd = util::Command::new("docker")
             .arg("run")
             .arg("--rm")
             .arg("--volume")
-            .arg(format!("{work_dir}:/w"))
+            .arg(&mounts[0])
             .arg("--workdir")
             .arg("/w");
 
@@ -37,27 +56,62 @@ impl Engine for DockerEngine {
             cmd = cmd.arg("--user").arg(format!("{uid}:{gid}"));
         }
 
-        if let Some(scratch) = spec.mounts.get(&MountRole::Scratch) {
-            let d = t.scratch_dir();
-            fs::create_dir_all(&d)?;
-            cmd = cmd.arg("--volume").arg(format!("{d}:/{scratch}"));
+        for mount in &mounts[1..] {
+            cmd = cmd.arg("--volume").arg(mount);
+        }
+
+        for (key, value) in &spec.env {
+            cmd = cmd.arg("--env").arg(format!("{key}={value}"));
+        }
+
+        match &options.gpus {
+            None | Some(Gpus::None) => {}
+            // a device list must be quoted, docker parses the value as CSV
+            Some(Gpus::Devices(devices)) => {
+                cmd = cmd
+                    .arg("--gpus")
+                    .arg(format!("\"device={}\"", devices.join(",")));
+            }
+            Some(gpus) => cmd = cmd.arg("--gpus").arg(gpus.to_string()),
+        }
+
+        let limits = &options.limits;
+        if let Some(cpus) = limits.cpus {
+            cmd = cmd.arg("--cpus").arg(cpus.to_string());
+        }
+        if let Some(memory) = &limits.memory {
+            cmd = cmd.arg("--memory").arg(memory);
+        }
+        if let Some(shm_size) = &limits.shm_size {
+            cmd = cmd.arg("--shm-size").arg(shm_size);
         }
 
         if let Some(entrypoint) = &spec.entrypoint {
             cmd = cmd.arg("--entrypoint").arg(entrypoint.as_str());
         }
 
-        let command = cmd.arg(app.container_image()).args(spec.args).live();
+        manifest.args = spec.args.clone();
+        manifest.mounts = mounts;
 
-        let result = command.call();
+        let command = cmd.arg(image).args(spec.args).live();
+
+        manifest.launched();
+        let result = command.try_call();
 
         let logs = format!(
-            "{command}\nprocess success: {}\n{}\n{}\n{}\n",
-            result.success, result.stdout, result.stderr, result.stderr
+            "{command}\n{}process success: {}\n{}\n{}\n{}\n",
+            options.rewrite_log(),
+            result.success,
+            result.stdout,
+            result.stderr,
+            result.stderr
         );
 
         fs::write(t.log_file_name(), logs)?;
 
+        manifest.finish(&result);
+        t.write_manifest(&manifest)?;
+
         if !result.success {
             eprintln!(
                 "{}",
@@ -83,14 +137,13 @@ impl Engine for DockerEngine {
         Ok(())
     }
 
-    fn install(&self, app: &dyn AppSpec) -> Result<()> {
-        util::Command::shell(format!(
-            "docker image inspect {0} >/dev/null 2>&1 || docker image pull {0}",
-            app.container_image()
-        ))
-        .live()
-        .exec()?;
-        Ok(())
+    fn install(&self, app: &dyn AppSpec, shared: bool) -> Result<()> {
+        if shared {
+            return Err(anyhow!(
+                "Docker images are stored by docker itself, `--shared` only applies to singularity, apptainer and native installs"
+            ));
+        }
+        pull(&config::settings().container_image(app))
     }
 
     fn clean(&self, app: &dyn AppSpec) -> Result<()> {
@@ -98,9 +151,46 @@ impl Engine for DockerEngine {
             .arg("image")
             .arg("rm")
             .arg("-f")
-            .arg(app.container_image())
+            .arg(config::settings().container_image(app))
             .live()
             .exec()?;
         Ok(())
     }
+
+    fn is_installed(&self, app: &dyn AppSpec) -> bool {
+        std::process::Command::new("docker")
+            .args(["image", "inspect", &config::settings().container_image(app)])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    fn update(&self, app: &dyn AppSpec) -> Result<Update> {
+        let image = config::settings().container_image(app);
+        let before = image::local_digest("docker", &image);
+        util::Command::new("docker")
+            .args(["image", "pull", &image])
+            .live()
+            .exec()?;
+        Ok(Update {
+            before,
+            after: image::local_digest("docker", &image),
+        })
+    }
+}
+
+/// Pull `image` unless it is already present locally.
+fn pull(image: &str) -> Result<()> {
+    let present = util::Command::new("docker")
+        .args(["image", "inspect", image])
+        .try_call()
+        .success;
+    if !present {
+        util::Command::new("docker")
+            .args(["image", "pull", image])
+            .live()
+            .exec()?;
+    }
+    Ok(())
 }
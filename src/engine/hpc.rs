@@ -1,15 +1,17 @@
-use std::fs;
+use std::{fs, io::Read};
 
 use camino::{Utf8Path, Utf8PathBuf};
 
 use crate::{
-    app::{AppSpec, MountRole},
-    engine::Engine,
-    telemetry::Telemetry,
-    util::{self, Command, dirs},
+    app::AppSpec,
+    cache, config,
+    engine::{Engine, Gpus, RunOptions, Update, container_binds},
+    image,
+    telemetry::{Manifest, Telemetry},
+    util::{self, Command, dirs, yansi::PaintExt},
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use yansi::Paint;
 
 pub struct HpcEngine(pub &'static str);
@@ -17,17 +19,32 @@ pub struct HpcEngine(pub &'static str);
 pub static SINGULARITY: HpcEngine = HpcEngine("singularity");
 pub static APPTAINER: HpcEngine = HpcEngine("apptainer");
 
+/// Version `rc update` reports for an image whose digest was never recorded.
+const UNKNOWN_DIGEST: &str = "unknown";
+
 impl Engine for HpcEngine {
-    fn execute(&self, app: &dyn AppSpec, args: Vec<String>, work_dir: &Utf8Path) -> Result<()> {
+    fn execute(
+        &self,
+        app: &dyn AppSpec,
+        args: Vec<String>,
+        work_dir: &Utf8Path,
+        options: &RunOptions,
+    ) -> Result<()> {
         // assert!(matches!(self.0, "singularity" | "apptainer"));
 
-        let spec = app.container_spec(args);
+        let mut manifest = Manifest::new(app, self.0, &args, options, work_dir)?;
+        let spec = options.container_spec(app, args);
 
         let engine = self.0;
 
-        let image_path = build_image(self, app.container_image());
+        let image = options
+            .image
+            .clone()
+            .unwrap_or_else(|| config::settings().container_image(app));
+        let image_path = self.image_path(&image)?;
+        manifest.set_image(&image);
 
-        let t = Telemetry::new(work_dir);
+        let t = Telemetry::for_run(work_dir, options.run_id)?;
 
         let verb = if spec.entrypoint.is_some() {
             "exec"
@@ -35,17 +52,46 @@ impl Engine for HpcEngine {
             "run"
         };
 
+        let mounts = container_binds(&spec, work_dir, &t)?;
+
         let mut cmd = util::Command::new(engine)
             .arg(verb)
             .arg("--bind")
-            .arg(format!("{work_dir}:/w"))
+            .arg(&mounts[0])
             .arg("--pwd")
             .arg("/w");
 
-        if let Some(scratch) = spec.mounts.get(&MountRole::Scratch) {
-            let d = t.scratch_dir();
-            fs::create_dir_all(&d)?;
-            cmd = cmd.arg("--bind").arg(format!("{d}:/{scratch}"));
+        for mount in &mounts[1..] {
+            cmd = cmd.arg("--bind").arg(mount);
+        }
+
+        for (key, value) in &spec.env {
+            cmd = cmd.arg("--env").arg(format!("{key}={value}"));
+        }
+
+        if let Some(gpus) = options.gpus.as_ref().filter(|g| **g != Gpus::None) {
+            cmd = cmd.arg("--nv");
+            if let Some(devices) = gpus.visible_devices() {
+                cmd = cmd
+                    .arg("--env")
+                    .arg(format!("CUDA_VISIBLE_DEVICES={devices}"));
+            }
+        }
+
+        // applied through cgroups, which needs cgroups v2 for unprivileged users
+        let limits = &options.limits;
+        if let Some(cpus) = limits.cpus {
+            cmd = cmd.arg("--cpus").arg(cpus.to_string());
+        }
+        if let Some(memory) = &limits.memory {
+            cmd = cmd.arg("--memory").arg(memory);
+        }
+        if limits.shm_size.is_some() {
+            eprintln!(
+                "{}",
+                format!("{engine} containers share the host /dev/shm, ignoring --shm-size")
+                    .orange()
+            );
         }
 
         cmd = cmd.arg(image_path.as_str());
@@ -54,17 +100,28 @@ impl Engine for HpcEngine {
             cmd = cmd.arg(entrypoint);
         }
 
+        manifest.args = spec.args.clone();
+        manifest.mounts = mounts;
+
         let command = cmd.args(spec.args).live();
 
-        let result = command.call();
+        manifest.launched();
+        let result = command.try_call();
 
         let logs = format!(
-            "{command}\nprocess success: {}\n{}\n{}\n{}\n",
-            result.success, result.stdout, result.stderr, result.stderr
+            "{command}\n{}process success: {}\n{}\n{}\n{}\n",
+            options.rewrite_log(),
+            result.success,
+            result.stdout,
+            result.stderr,
+            result.stderr
         );
 
         fs::write(t.log_file_name(), logs)?;
 
+        manifest.finish(&result);
+        t.write_manifest(&manifest)?;
+
         if !result.success {
             eprintln!(
                 "{}",
@@ -90,44 +147,243 @@ impl Engine for HpcEngine {
         Ok(())
     }
 
-    fn install(&self, app: &dyn AppSpec) -> Result<()> {
-        build_image(self, app.container_image());
+    fn install(&self, app: &dyn AppSpec, shared: bool) -> Result<()> {
+        let image = config::settings().container_image(app);
+        build_image(self, &image, &image, shared, false)?;
         Ok(())
     }
 
     fn clean(&self, app: &dyn AppSpec) -> Result<()> {
-        let image_path = hpc_image_path(app.container_image());
+        let image_path = cached_image_path(&config::settings().container_image(app));
         if image_path.exists() {
             fs::remove_file(&image_path)?;
         }
+        let _ = fs::remove_file(digest_file(&image_path));
         // else {
         //     println!("No image found at {:?}, nothing to clean.", image_path);
         // }
 
         Ok(())
     }
+
+    fn prepare(&self, app: &dyn AppSpec, options: &RunOptions) -> Result<()> {
+        let image = options
+            .image
+            .clone()
+            .unwrap_or_else(|| config::settings().container_image(app));
+        self.image_path(&image)?;
+        Ok(())
+    }
+
+    fn is_installed(&self, app: &dyn AppSpec) -> bool {
+        hpc_image_path(&config::settings().container_image(app)).exists()
+    }
+
+    /// `.sif` files do not record the digest they were pulled at, so it is kept next to them once
+    /// `rc update` has resolved it. Images without a recorded digest are rebuilt, from the resolved
+    /// digest so the recorded one matches the image even if the tag moves meanwhile.
+    fn update(&self, app: &dyn AppSpec) -> Result<Update> {
+        let image = config::settings().container_image(app);
+        if let Some(path) = shared_image_path(&image) {
+            println!(
+                "{}",
+                format!(
+                    "{} uses the shared image {path}, leaving it to the admins",
+                    app.name()
+                )
+                .dim()
+            );
+            let digest = recorded_digest(&path);
+            return Ok(Update {
+                before: digest.clone(),
+                after: digest,
+            });
+        }
+
+        let path = cached_image_path(&image);
+        // an image pulled before digests were recorded is installed, at an unknown version
+        let before = path
+            .exists()
+            .then(|| recorded_digest(&path).unwrap_or_else(|| UNKNOWN_DIGEST.into()));
+        let digest = image::resolve_digest(&image)?;
+        if before.as_deref() != Some(digest.as_str()) {
+            let source = image::ImageRef::parse(&image).pinned(&digest);
+            build_image(self, &image, &source, false, true)?;
+        }
+        fs::write(digest_file(&path), &digest)?;
+        Ok(Update {
+            before,
+            after: Some(digest),
+        })
+    }
+}
+
+impl HpcEngine {
+    /// `.sif` to run `image` from: the shared installation, or the per-user cache where it is
+    /// built when missing.
+    fn image_path(&self, image: &str) -> Result<Utf8PathBuf> {
+        if let Some(path) = shared_image_path(image) {
+            return Ok(path);
+        }
+        let path = build_image(self, image, image, false, false)?;
+        cache::touch(&path)?;
+        Ok(path)
+    }
 }
 
-fn build_image(engine: &HpcEngine, image: &str) -> Utf8PathBuf {
-    let image_path = hpc_image_path(image);
-    if !image_path.exists() {
-        println!("Could not find {}, rebuilding...", image_path.green());
-        Command::new(engine.0)
-            .args(["pull", image_path.as_str(), &format!("docker://{}", image)])
-            .live()
-            .exec()
-            .expect("error building image");
+/// Build the `.sif` for `image` from the registry reference `source` (usually `image` itself) unless
+/// it exists (or, with `replace`, over the existing one), into the per-user cache or, when `shared`, into the group-readable shared root. Concurrent builds
+/// (e.g. a job array on a shared filesystem) are serialized by a lock file, and each build pulls
+/// into a temporary file that is validated and then renamed into place, so an image path never
+/// holds a partial image.
+fn build_image(
+    engine: &HpcEngine,
+    image: &str,
+    source: &str,
+    shared: bool,
+    replace: bool,
+) -> Result<Utf8PathBuf> {
+    let image_path = if shared {
+        let root = shared_images_root()?;
+        util::share_with_group(&root)?;
+        image_file(&root, image)
+    } else {
+        let root = hpc_images_root();
+        fs::create_dir_all(&root).with_context(|| format!("failed to create {root}"))?;
+        image_file(&root, image)
+    };
+    if image_path.exists() && !replace {
+        return Ok(image_path);
     }
 
-    image_path
+    let lock_path = image_path.with_extension("sif.lock");
+    let lock = fs::File::create(&lock_path)
+        .with_context(|| format!("failed to create lock file {lock_path}"))?;
+    if shared {
+        util::share_with_group(&lock_path)?;
+    }
+    if lock.try_lock().is_err() {
+        println!(
+            "Waiting for another rc process to finish building {}...",
+            image_path.green()
+        );
+        lock.lock()
+            .with_context(|| format!("failed to lock {lock_path}"))?;
+    }
+
+    // built by the process that held the lock
+    if image_path.exists() && !replace {
+        return Ok(image_path);
+    }
+    remove_partial_builds(&image_path)?;
+
+    match replace && image_path.exists() {
+        true => println!("Replacing {}...", image_path.green()),
+        false => println!("Could not find {}, rebuilding...", image_path.green()),
+    }
+    let tmp_path = Utf8PathBuf::from(format!(
+        "{image_path}.{}.{}.tmp",
+        util::hostname(),
+        std::process::id()
+    ));
+    let result = Command::new(engine.0)
+        .args(["pull", tmp_path.as_str(), &format!("docker://{source}")])
+        .live()
+        .exec()
+        .map_err(anyhow::Error::from)
+        .and_then(|()| validate_sif(&tmp_path))
+        .and_then(|()| {
+            if shared {
+                util::share_with_group(&tmp_path)?;
+            }
+            Ok(())
+        })
+        .and_then(|()| Ok(fs::rename(&tmp_path, &image_path)?));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.context(format!("failed to build {image_path} from {source}")));
+    }
+
+    Ok(image_path)
+}
+
+/// Remove temporary files left behind by interrupted builds of `image_path`, only called while
+/// holding its lock.
+fn remove_partial_builds(image_path: &Utf8Path) -> Result<()> {
+    let (Some(dir), Some(name)) = (image_path.parent(), image_path.file_name()) else {
+        return Ok(());
+    };
+    for entry in dir.read_dir_utf8()? {
+        let path = entry?.into_path();
+        let partial = path
+            .file_name()
+            .and_then(|f| f.strip_prefix(name))
+            .is_some_and(|rest| rest.starts_with('.') && rest.ends_with(".tmp"));
+        if partial {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
 }
 
-fn hpc_images_root() -> Utf8PathBuf {
-    let root = dirs::cache_root().join("hpc");
-    std::fs::create_dir_all(&root).unwrap();
-    root
+/// Check that `path` is a SIF image: a 32 byte launch script followed by the `SIF_MAGIC` tag.
+fn validate_sif(path: &Utf8Path) -> Result<()> {
+    let mut header = [0u8; 41];
+    let valid = fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .is_ok()
+        && &header[32..] == b"SIF_MAGIC";
+    if !valid {
+        return Err(anyhow!("{path} is not a valid SIF image"));
+    }
+    Ok(())
+}
+
+/// Directory of the per-user `.sif` images, only looked up here, it is created when an image is
+/// built.
+pub fn hpc_images_root() -> Utf8PathBuf {
+    dirs::cache_root().join("hpc")
 }
 
+/// `.sif` used for `image`: the shared installation when there is one, otherwise the per-user
+/// cache.
 pub fn hpc_image_path(image: &str) -> Utf8PathBuf {
-    hpc_images_root().join(format!("{}.sif", image.replace('/', "-")))
+    shared_image_path(image).unwrap_or_else(|| cached_image_path(image))
+}
+
+/// `.sif` for `image` in the per-user cache.
+pub fn cached_image_path(image: &str) -> Utf8PathBuf {
+    image_file(&hpc_images_root(), image)
+}
+
+/// `.sif` for `image` installed under the shared root, if it exists.
+fn shared_image_path(image: &str) -> Option<Utf8PathBuf> {
+    let path = image_file(&dirs::shared_root()?.join("hpc"), image);
+    path.exists().then_some(path)
+}
+
+/// Directory of shared `.sif` images, created on demand by `rc install --shared`.
+fn shared_images_root() -> Result<Utf8PathBuf> {
+    let root = dirs::shared_root()
+        .ok_or_else(|| anyhow!("`--shared` needs `shared.root` or RC_SHARED_ROOT to be set"))?
+        .join("hpc");
+    fs::create_dir_all(&root).with_context(|| format!("failed to create {root}"))?;
+    Ok(root)
+}
+
+/// File next to the `.sif` at `image_path` holding the registry digest it was pulled at.
+pub fn digest_file(image_path: &Utf8Path) -> Utf8PathBuf {
+    image_path.with_extension("sif.digest")
+}
+
+fn recorded_digest(image_path: &Utf8Path) -> Option<String> {
+    fs::read_to_string(digest_file(image_path))
+        .ok()
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+}
+
+fn image_file(images_root: &Utf8Path, image: &str) -> Utf8PathBuf {
+    images_root.join(format!("{}.sif", image.replace('/', "-")))
 }
@@ -1,4 +1,7 @@
-use std::fs;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
 
 use anyhow::Result;
 use anyhow::anyhow;
@@ -7,47 +10,151 @@ use camino::Utf8PathBuf;
 use yansi::Paint;
 
 use crate::app::AppSpec;
-use crate::engine::Engine;
-use crate::telemetry::Telemetry;
-use crate::util::Command;
-use crate::util::dirs::cache_root;
-use crate::util::ensure_dir_signature;
+use crate::cache;
+use crate::config;
+use crate::engine::{Engine, Gpus, RunOptions, THREAD_ENV_VARS, Update};
+use crate::telemetry::{Manifest, Telemetry};
+use crate::util::dirs::{self, cache_root};
+use crate::util::{self, Command};
+use crate::util::{dir_signature, dir_signature_is_current, ensure_dir_signature, yansi::PaintExt};
 
 pub struct NativeEngine;
 pub static NATIVE: NativeEngine = NativeEngine;
 
 impl Engine for NativeEngine {
-    fn execute(&self, app: &dyn AppSpec, args: Vec<String>, work_dir: &Utf8Path) -> Result<()> {
-        let spec = app.native_spec(args, work_dir);
+    fn execute(
+        &self,
+        app: &dyn AppSpec,
+        args: Vec<String>,
+        work_dir: &Utf8Path,
+        options: &RunOptions,
+    ) -> Result<()> {
+        let mut manifest = Manifest::new(app, "none", &args, options, work_dir)?;
+        let spec = options.native_spec(app, args, work_dir);
 
-        let pixi_evn_root = build_pixi_env(app)?;
+        // native runs cannot be capped, but sizing the thread pools keeps them within `--cpus`
+        let threads = options.limits.threads().map(|t| t.to_string());
+        let thread_env = threads
+            .iter()
+            .flat_map(|t| THREAD_ENV_VARS.map(|var| (var, t.as_str())));
+        if options.limits.memory.is_some() || options.limits.shm_size.is_some() {
+            eprintln!(
+                "{}",
+                "Native runs do not support --memory or --shm-size, ignoring them".orange()
+            );
+        }
 
-        let new_args = spec
-            .args
-            // .into_iter()
-            // .map(|arg| shell_escape::escape(arg.into()).to_string())
-            // .collect::<Vec<_>>()
-            .join(" ");
+        let mut command = match config::settings().native_prefix(app) {
+            Some(prefix) => {
+                if !prefix.is_dir() {
+                    return Err(anyhow!(
+                        "existing {} installation {prefix} does not exist, check `rc config show`",
+                        app.name()
+                    ));
+                }
+                let prefix_env = prefix_env(&prefix);
+                // arguments only see the installation's variables and the ones the Pixi recipe
+                // would define, which an existing installation takes from the environment
+                let recipe_vars: Vec<String> = recipe_env(app.pixi_recipe().unwrap_or_default())?
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect();
+                let vars: BTreeMap<String, String> = recipe_vars
+                    .iter()
+                    .filter_map(|name| Some((name.clone(), std::env::var(name).ok()?)))
+                    .chain(prefix_env.clone())
+                    .chain(spec.env.clone())
+                    .collect();
+                let missing: BTreeSet<String> = spec
+                    .args
+                    .iter()
+                    .map(String::as_str)
+                    .chain(spec.cwd.as_ref().map(|d| d.as_str()))
+                    .flat_map(|arg| unresolved(arg, &vars))
+                    .filter(|name| recipe_vars.contains(name))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(anyhow!(
+                        "the existing {} installation {prefix} does not define {}, set them in the environment or with `--env`",
+                        app.name(),
+                        missing
+                            .iter()
+                            .map(|name| format!("${name}"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+                let argv: Vec<String> = spec.args.iter().map(|arg| expand(arg, &vars)).collect();
+                let cwd = match &spec.cwd {
+                    Some(dir) => app_dir(&prefix, &expand(dir.as_str(), &vars)),
+                    None => work_dir.to_path_buf(),
+                };
+                let (program, program_args) = argv
+                    .split_first()
+                    .ok_or_else(|| anyhow!("no program given to run with {}", app.name()))?;
 
-        let command = Command::new("pixi")
-            // .cd(&pixi_evn_root)
-            .arg("run")
-            .args(["--manifest-path", pixi_evn_root.join("pixi.toml").as_str()])
-            .arg("execute")
-            .arg(new_args)
-            .live();
+                println!("Using existing installation: {}", prefix.bright_blue());
+                manifest.native_prefix = Some(prefix);
+                manifest.args = argv.clone();
+                Command::new(program)
+                    .cd(&cwd)
+                    .args(program_args)
+                    .envs(prefix_env)
+            }
+            None => {
+                let pixi_evn_root = match shared_pixi_evn_root(app) {
+                    Some(root) => root,
+                    None => {
+                        let root = build_pixi_env(app, false)?;
+                        cache::touch(&root)?;
+                        root
+                    }
+                };
+
+                manifest.pixi_recipe_hash = app
+                    .pixi_recipe()
+                    .map(|recipe| blake3::hash(recipe.as_bytes()).to_hex().to_string());
+
+                let vars = activation_env(app.pixi_recipe().unwrap_or_default(), &pixi_evn_root)?;
+                let argv: Vec<String> = spec.args.iter().map(|arg| expand(arg, &vars)).collect();
+                let cwd = match &spec.cwd {
+                    Some(dir) => pixi_evn_root.join(expand(dir.as_str(), &vars)),
+                    None => work_dir.to_path_buf(),
+                };
+                manifest.args = argv.clone();
+
+                // `--executable` keeps pixi from treating the program as one of the recipe's tasks
+                Command::new("pixi")
+                    .cd(&cwd)
+                    .arg("run")
+                    .args(["--manifest-path", pixi_evn_root.join("pixi.toml").as_str()])
+                    .args(["--executable", "--"])
+                    .args(argv)
+            }
+        }
+        .envs(thread_env)
+        .envs(&spec.env)
+        .live();
+        if let Some(devices) = options.gpus.as_ref().and_then(Gpus::visible_devices) {
+            command = command.env("CUDA_VISIBLE_DEVICES", devices);
+        }
 
-        let result = command.call();
+        // the run id is reserved before launching, so concurrent runs are numbered in start order
+        let t = Telemetry::for_run(work_dir, options.run_id)?;
 
-        let t = Telemetry::new(work_dir);
+        manifest.launched();
+        let result = command.try_call();
 
         let logs = format!(
-            "{command}\nprocess success: {}\n{}\n{}\n{}\n",
-            result.success, result.stdout, result.stderr, result.stderr
+            "{command}\nprocess success: {}\n{}\n{}\n",
+            result.success, result.stdout, result.stderr
         );
 
         fs::write(t.log_file_name(), logs)?;
 
+        manifest.finish(&result);
+        t.write_manifest(&manifest)?;
+
         if !result.success {
             eprintln!(
                 "{}",
@@ -74,13 +181,21 @@ impl Engine for NativeEngine {
         Ok(())
     }
 
-    fn install(&self, app: &dyn AppSpec) -> Result<()> {
-        build_pixi_env(app)?;
+    fn install(&self, app: &dyn AppSpec, shared: bool) -> Result<()> {
+        if let Some(prefix) = config::settings().native_prefix(app) {
+            println!(
+                "{} uses the existing installation at {}, nothing to install",
+                app.name(),
+                prefix.bright_blue()
+            );
+            return Ok(());
+        }
+        build_pixi_env(app, shared)?;
         Ok(())
     }
 
     fn clean(&self, app: &dyn AppSpec) -> Result<()> {
-        let pixi_evn_root = pixi_evn_root(app);
+        let pixi_evn_root = cached_pixi_evn_root(app);
 
         if pixi_evn_root.exists() {
             fs::remove_dir_all(&pixi_evn_root)?;
@@ -88,18 +203,71 @@ impl Engine for NativeEngine {
 
         Ok(())
     }
+
+    fn prepare(&self, app: &dyn AppSpec, _options: &RunOptions) -> Result<()> {
+        if config::settings().native_prefix(app).is_none() && shared_pixi_evn_root(app).is_none() {
+            build_pixi_env(app, false)?;
+        }
+        Ok(())
+    }
+
+    fn is_installed(&self, app: &dyn AppSpec) -> bool {
+        match config::settings().native_prefix(app) {
+            Some(prefix) => prefix.is_dir(),
+            None => app.pixi_recipe().is_some() && pixi_env_is_current(app),
+        }
+    }
+
+    /// Rebuilds the environment when its recipe changed, the signature is its version.
+    fn update(&self, app: &dyn AppSpec) -> Result<Update> {
+        if let Some(prefix) = config::settings().native_prefix(app) {
+            println!(
+                "{}",
+                format!("{} uses the existing installation at {prefix}", app.name()).dim()
+            );
+            return Ok(Update {
+                before: None,
+                after: None,
+            });
+        }
+        if let Some(root) = shared_pixi_evn_root(app) {
+            let signature = dir_signature(&root);
+            return Ok(Update {
+                before: signature.clone(),
+                after: signature,
+            });
+        }
+
+        let before = dir_signature(&cached_pixi_evn_root(app));
+        let root = build_pixi_env(app, false)?;
+        Ok(Update {
+            before,
+            after: dir_signature(&root),
+        })
+    }
 }
 
-fn build_pixi_env(app: &dyn AppSpec) -> Result<Utf8PathBuf, anyhow::Error> {
+/// Build the Pixi environment of `app` unless it is current, into the per-user cache or, when
+/// `shared`, into the group-readable shared root.
+fn build_pixi_env(app: &dyn AppSpec, shared: bool) -> Result<Utf8PathBuf, anyhow::Error> {
     let pixi_recipe = app
         .pixi_recipe()
         .unwrap_or_else(|| unimplemented!("Native run for {} is not supported", app.name().red()));
 
     check_if_pixi_is_installed()?;
 
-    let pixi_evn_root = pixi_evn_root(app);
+    let pixi_evn_root = if shared {
+        let envs_root = dirs::shared_root()
+            .ok_or_else(|| anyhow!("`--shared` needs `shared.root` or RC_SHARED_ROOT to be set"))?
+            .join("native");
+        fs::create_dir_all(&envs_root)?;
+        util::share_with_group(&envs_root)?;
+        envs_root.join(app.name())
+    } else {
+        cached_pixi_evn_root(app)
+    };
 
-    ensure_dir_signature(&pixi_evn_root, &[app.name(), pixi_recipe], |d| {
+    ensure_dir_signature(&pixi_evn_root, &pixi_env_signature(app), |d| {
         std::fs::write(d.join("pixi.toml"), pixi_recipe)?;
         Command::new("pixi")
             .cd(d)
@@ -109,11 +277,156 @@ fn build_pixi_env(app: &dyn AppSpec) -> Result<Utf8PathBuf, anyhow::Error> {
             .exec()?;
         Ok(())
     })?;
+    if shared {
+        util::share_with_group(&pixi_evn_root)?;
+    }
     Ok(pixi_evn_root)
 }
 
+/// Signature parts of the native environment of `app`, a changed recipe triggers a rebuild.
+fn pixi_env_signature(app: &dyn AppSpec) -> [&str; 2] {
+    [app.name(), app.pixi_recipe().unwrap_or_default()]
+}
+
+/// Whether the native environment of `app` is built from its current Pixi recipe.
+pub fn pixi_env_is_current(app: &dyn AppSpec) -> bool {
+    dir_signature_is_current(&pixi_evn_root(app), &pixi_env_signature(app)).unwrap_or(false)
+}
+
+/// Variables native specs may reference: `PIXI_PROJECT_ROOT` and `CONDA_PREFIX` of the environment
+/// at `root`, then the `[activation.env]` of `recipe`, each expanded with the ones before it.
+fn activation_env(recipe: &str, root: &Utf8Path) -> Result<BTreeMap<String, String>> {
+    let mut vars = BTreeMap::from([
+        ("PIXI_PROJECT_ROOT".to_string(), root.to_string()),
+        (
+            "CONDA_PREFIX".to_string(),
+            root.join(".pixi/envs/default").to_string(),
+        ),
+    ]);
+
+    for (key, value) in recipe_env(recipe)? {
+        let value = expand(&value, &vars);
+        vars.insert(key, value);
+    }
+    Ok(vars)
+}
+
+/// Variables of the `[activation.env]` table of `recipe` in order, with their unexpanded values.
+fn recipe_env(recipe: &str) -> Result<Vec<(String, String)>> {
+    let doc: toml_edit::DocumentMut = recipe.parse()?;
+    let env = doc
+        .get("activation")
+        .and_then(|a| a.get("env"))
+        .and_then(|e| e.as_table_like());
+    Ok(env
+        .into_iter()
+        .flat_map(|e| e.iter())
+        .filter_map(|(key, value)| Some((key.to_string(), value.as_str()?.to_string())))
+        .collect())
+}
+
+/// Replace `$VAR` and `${VAR}` references to `vars` in `s`, anything else is kept as is.
+fn expand(s: &str, vars: &BTreeMap<String, String>) -> String {
+    expand_with(s, |name| vars.get(name).cloned())
+}
+
+/// Names of the `$VAR` and `${VAR}` references in `s` that `vars` does not define.
+fn unresolved(s: &str, vars: &BTreeMap<String, String>) -> Vec<String> {
+    let mut missing = Vec::new();
+    expand_with(s, |name| {
+        let value = vars.get(name).cloned();
+        if value.is_none() {
+            missing.push(name.to_string());
+        }
+        value
+    });
+    missing
+}
+
+fn expand_with(s: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        match (!name.is_empty()).then(|| lookup(name)).flatten() {
+            Some(value) => {
+                out.push_str(&value);
+                rest = &after[len..];
+            }
+            _ => {
+                out.push('$');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Environment of a run from the existing installation at `prefix`: its `bin` directory first on
+/// `PATH`, and `CONDA_PREFIX` or `VIRTUAL_ENV` when it is a conda environment or a virtualenv.
+fn prefix_env(prefix: &Utf8Path) -> BTreeMap<String, String> {
+    let mut env = BTreeMap::new();
+    let bin = prefix.join("bin");
+    if bin.is_dir() {
+        let path = std::env::var("PATH").unwrap_or_default();
+        let path = match path.is_empty() {
+            true => bin.to_string(),
+            false => format!("{bin}:{path}"),
+        };
+        env.insert("PATH".to_string(), path);
+    }
+    if prefix.join("conda-meta").is_dir() {
+        env.insert("CONDA_PREFIX".to_string(), prefix.to_string());
+    } else if prefix.join("pyvenv.cfg").is_file() {
+        env.insert("VIRTUAL_ENV".to_string(), prefix.to_string());
+    }
+    env
+}
+
+/// Directory `dir` of the app installed at `prefix`, laid out like its Pixi environment when the
+/// directory exists there, otherwise `prefix` is taken to be that directory itself.
+fn app_dir(prefix: &Utf8Path, dir: &str) -> Utf8PathBuf {
+    let path = prefix.join(dir);
+    match path.is_dir() {
+        true => path,
+        false => prefix.to_path_buf(),
+    }
+}
+
+pub fn pixi_envs_root() -> Utf8PathBuf {
+    cache_root().join("native")
+}
+
+/// Native environment used for `app`: the shared installation when it is current, otherwise the
+/// per-user cache.
 pub fn pixi_evn_root(app: &dyn AppSpec) -> Utf8PathBuf {
-    cache_root().join(format!("native/{}", app.name()))
+    shared_pixi_evn_root(app).unwrap_or_else(|| cached_pixi_evn_root(app))
+}
+
+/// Native environment of `app` in the per-user cache.
+pub fn cached_pixi_evn_root(app: &dyn AppSpec) -> Utf8PathBuf {
+    pixi_envs_root().join(app.name())
+}
+
+/// Native environment of `app` installed under the shared root, if it is current.
+fn shared_pixi_evn_root(app: &dyn AppSpec) -> Option<Utf8PathBuf> {
+    let root = dirs::shared_root()?.join("native").join(app.name());
+    dir_signature_is_current(&root, &pixi_env_signature(app))
+        .unwrap_or(false)
+        .then_some(root)
 }
 
 /// Check if Pixi is installed, fail if not
@@ -127,3 +440,73 @@ fn check_if_pixi_is_installed() -> Result<()> {
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("A".to_string(), "/a".to_string()),
+            ("A_B".to_string(), "x y".to_string()),
+        ])
+    }
+
+    #[test]
+    fn known_variables_are_expanded() {
+        assert_eq!(expand("$A/w.pt", &vars()), "/a/w.pt");
+        assert_eq!(expand("${A}_B", &vars()), "/a_B");
+        assert_eq!(expand("--p=$A_B", &vars()), "--p=x y");
+        assert_eq!(expand("$A$A", &vars()), "/a/a");
+    }
+
+    #[test]
+    fn other_dollars_are_kept() {
+        for s in ["$HOME/x", "cost: 5$", "${A", "$", "${}", "it's $Unknown"] {
+            assert_eq!(expand(s, &vars()), s);
+        }
+    }
+
+    #[test]
+    fn unresolved_variables_are_named() {
+        assert_eq!(unresolved("$A/${A_B}/w.pt", &vars()), Vec::<String>::new());
+        assert_eq!(unresolved("$MISSING/w.pt", &vars()), ["MISSING"]);
+        assert_eq!(unresolved("--y=${B}", &vars()), ["B"]);
+        assert!(unresolved("cost: 5$", &vars()).is_empty());
+    }
+
+    #[test]
+    fn recipes_leave_the_command_line_to_rc() {
+        use strum::IntoEnumIterator;
+        for app in crate::app::App::iter() {
+            let Some(recipe) = app.spec().pixi_recipe() else {
+                continue;
+            };
+            let doc: toml_edit::DocumentMut = recipe.parse().unwrap();
+            assert!(
+                doc.get("tasks").and_then(|t| t.get("execute")).is_none(),
+                "{app} recipe has an `execute` task, native runs are built by its `native_spec`"
+            );
+        }
+    }
+
+    #[test]
+    fn activation_env_is_expanded_in_order() {
+        let recipe = r#"
+[activation.env]
+WEIGHTS_DIR = "$PIXI_PROJECT_ROOT/clone"
+WEIGHTS = "$WEIGHTS_DIR/model_params"
+LD_LIBRARY_PATH = "$CONDA_PREFIX/lib:$LD_LIBRARY_PATH"
+"#;
+        let vars = activation_env(recipe, Utf8Path::new("/env")).unwrap();
+        assert_eq!(vars["WEIGHTS"], "/env/clone/model_params");
+        assert_eq!(
+            vars["LD_LIBRARY_PATH"],
+            "/env/.pixi/envs/default/lib:$LD_LIBRARY_PATH"
+        );
+
+        let inline = "[activation]\nenv = { DGLBACKEND = \"pytorch\" }\n";
+        let vars = activation_env(inline, Utf8Path::new("/env")).unwrap();
+        assert_eq!(vars["DGLBACKEND"], "pytorch");
+    }
+}
@@ -0,0 +1,248 @@
+use std::{fs, process::Stdio};
+
+use anyhow::{Result, anyhow};
+use camino::Utf8Path;
+use yansi::Paint;
+
+use crate::{
+    app::AppSpec,
+    config,
+    engine::{Engine, Gpus, RunOptions, Update, container_binds},
+    image,
+    telemetry::{Manifest, Telemetry},
+    util::{self},
+};
+
+pub struct PodmanEngine;
+pub static PODMAN: PodmanEngine = PodmanEngine;
+
+impl Engine for PodmanEngine {
+    fn execute(
+        &self,
+        app: &dyn AppSpec,
+        args: Vec<String>,
+        work_dir: &Utf8Path,
+        options: &RunOptions,
+    ) -> Result<()> {
+        let mut manifest = Manifest::new(app, "podman", &args, options, work_dir)?;
+        let spec = options.container_spec(app, args);
+        let image = qualified(
+            &options
+                .image
+                .clone()
+                .unwrap_or_else(|| config::settings().container_image(app)),
+        );
+
+        pull(&image)?;
+        manifest.set_image(&image);
+        if manifest.digest.is_none() {
+            manifest.digest = image::local_digest("podman", &image);
+        }
+
+        let t = Telemetry::for_run(work_dir, options.run_id)?;
+
+        let mounts = container_binds(&spec, work_dir, &t)?;
+
+        // rootless Podman maps the invoking user into the container, so files written to the
+        // mounts keep the user's ownership without `--user`
+        let mut cmd = util::Command::new("podman")
+            .arg("run")
+            .arg("--rm")
+            .arg("--userns=keep-id");
+
+        // relabeling changes the SELinux context of the whole host tree, so only the directories
+        // rc owns for the run are labeled unless the user opts in for the others
+        let relabel_all = config::settings().podman_relabel_mounts();
+        let own = [work_dir.to_string(), t.scratch_dir().to_string()];
+        for mount in &mounts {
+            let host = mount.split(':').next().unwrap_or_default();
+            cmd = cmd
+                .arg("--volume")
+                .arg(match relabel_all || own.iter().any(|d| d == host) {
+                    true => selinux_labeled(mount),
+                    false => mount.clone(),
+                });
+        }
+
+        cmd = cmd.arg("--workdir").arg("/w");
+
+        for (key, value) in &spec.env {
+            cmd = cmd.arg("--env").arg(format!("{key}={value}"));
+        }
+
+        // GPUs are passed as CDI devices, see `nvidia-ctk cdi generate`
+        if let Some(gpus) = options.gpus.as_ref().filter(|g| **g != Gpus::None) {
+            for id in gpus.device_ids().unwrap_or_else(|| vec!["all".into()]) {
+                cmd = cmd.arg("--device").arg(format!("nvidia.com/gpu={id}"));
+            }
+        }
+
+        let limits = &options.limits;
+        if let Some(cpus) = limits.cpus {
+            cmd = cmd.arg("--cpus").arg(cpus.to_string());
+        }
+        if let Some(memory) = &limits.memory {
+            cmd = cmd.arg("--memory").arg(memory);
+        }
+        if let Some(shm_size) = &limits.shm_size {
+            cmd = cmd.arg("--shm-size").arg(shm_size);
+        }
+
+        if let Some(entrypoint) = &spec.entrypoint {
+            cmd = cmd.arg("--entrypoint").arg(entrypoint.as_str());
+        }
+
+        manifest.args = spec.args.clone();
+        manifest.mounts = mounts;
+
+        let command = cmd.arg(image).args(spec.args).live();
+
+        manifest.launched();
+        let result = command.try_call();
+
+        let logs = format!(
+            "{command}\n{}process success: {}\n{}\n{}\n",
+            options.rewrite_log(),
+            result.success,
+            result.stdout,
+            result.stderr
+        );
+
+        fs::write(t.log_file_name(), logs)?;
+
+        manifest.finish(&result);
+        t.write_manifest(&manifest)?;
+
+        if !result.success {
+            eprintln!(
+                "{}",
+                "Podman container exited with non-zero status"
+                    .bright_red()
+                    .bold()
+            );
+            return Err(anyhow::anyhow!(
+                "Podman container exited with non-zero status"
+            ));
+        }
+
+        println!(
+            "{}",
+            format!(
+                "The exact command line used and full log saved into {:?}\nScratch dir for this run is: {:?}\n",
+                t.log_file_name(), t.scratch_dir()
+            )
+            .blue()
+            .dim()
+        );
+
+        Ok(())
+    }
+
+    fn install(&self, app: &dyn AppSpec, shared: bool) -> Result<()> {
+        if shared {
+            return Err(anyhow!(
+                "Podman images are stored by podman itself, `--shared` only applies to singularity, apptainer and native installs"
+            ));
+        }
+        pull(&qualified(&config::settings().container_image(app)))
+    }
+
+    fn clean(&self, app: &dyn AppSpec) -> Result<()> {
+        util::Command::new("podman")
+            .arg("image")
+            .arg("rm")
+            .arg("--force")
+            .arg("--ignore")
+            .arg(qualified(&config::settings().container_image(app)))
+            .live()
+            .exec()?;
+        Ok(())
+    }
+
+    fn is_installed(&self, app: &dyn AppSpec) -> bool {
+        std::process::Command::new("podman")
+            .args(["image", "exists"])
+            .arg(qualified(&config::settings().container_image(app)))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    fn update(&self, app: &dyn AppSpec) -> Result<Update> {
+        let image = qualified(&config::settings().container_image(app));
+        let before = image::local_digest("podman", &image);
+        util::Command::new("podman")
+            .args(["image", "pull", &image])
+            .live()
+            .exec()?;
+        Ok(Update {
+            before,
+            after: image::local_digest("podman", &image),
+        })
+    }
+}
+
+/// Pull `image` unless it is already present locally.
+fn pull(image: &str) -> Result<()> {
+    let present = util::Command::new("podman")
+        .args(["image", "exists", image])
+        .try_call()
+        .success;
+    if !present {
+        util::Command::new("podman")
+            .args(["image", "pull", image])
+            .live()
+            .exec()?;
+    }
+    Ok(())
+}
+
+/// Fully qualify Docker Hub short names, Podman would otherwise prompt for a registry or refuse
+/// them depending on `unqualified-search-registries`.
+fn qualified(image: &str) -> String {
+    let registry = image.split_once('/').map(|(first, _)| first);
+    match registry {
+        Some(r) if r.contains(['.', ':']) || r == "localhost" => image.into(),
+        Some(_) => format!("docker.io/{image}"),
+        None => format!("docker.io/library/{image}"),
+    }
+}
+
+/// Add the shared SELinux label `z` to a `host:container[:ro]` volume, so containers may access
+/// it on enforcing hosts while the host and other containers keep access too.
+fn selinux_labeled(volume: &str) -> String {
+    match volume.strip_suffix(":ro") {
+        Some(volume) => format!("{volume}:ro,z"),
+        None => format!("{volume}:z"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qualified_short_names() {
+        assert_eq!(
+            qualified("rosettacommons/rosetta:serial"),
+            "docker.io/rosettacommons/rosetta:serial"
+        );
+        assert_eq!(qualified("ubuntu"), "docker.io/library/ubuntu");
+        assert_eq!(
+            qualified("ghcr.io/sokrypton/colabfold:1.6.0"),
+            "ghcr.io/sokrypton/colabfold:1.6.0"
+        );
+        assert_eq!(
+            qualified("localhost:5000/rosetta"),
+            "localhost:5000/rosetta"
+        );
+        assert_eq!(qualified("localhost/rosetta"), "localhost/rosetta");
+    }
+
+    #[test]
+    fn selinux_labels() {
+        assert_eq!(selinux_labeled("/data:/w"), "/data:/w:z");
+        assert_eq!(selinux_labeled("/refs:/refs:ro"), "/refs:/refs:ro,z");
+    }
+}
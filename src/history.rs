@@ -0,0 +1,231 @@
+use std::{fs, time::Duration};
+
+use anyhow::Result;
+use camino::Utf8Path;
+use serde::Serialize;
+use yansi::Paint;
+
+use crate::{
+    app::App,
+    telemetry::{Manifest, Telemetry},
+    util::yansi::PaintExt,
+};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failed,
+}
+
+/// Selection for `rc history`, runs must match every criterion that is set.
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    pub app: Option<App>,
+    pub status: Option<Status>,
+    pub tag: Option<String>,
+}
+
+/// A previous run, `manifest` is `None` for runs recorded before manifests were written.
+#[derive(Serialize)]
+struct Run {
+    id: u32,
+    #[serde(flatten)]
+    manifest: Option<Manifest>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, run: &Run) -> bool {
+        let Some(m) = &run.manifest else {
+            return self.app.is_none() && self.status.is_none() && self.tag.is_none();
+        };
+
+        let app_matches = self.app.is_none_or(|a| a.spec().name() == m.app);
+        let status_matches = match self.status {
+            None => true,
+            Some(Status::Success) => m.success == Some(true),
+            Some(Status::Failed) => m.success == Some(false),
+        };
+        let tag_matches = self.tag.as_ref().is_none_or(|t| m.tags.contains(t));
+
+        app_matches && status_matches && tag_matches
+    }
+}
+
+fn runs(working_dir: &Utf8Path) -> Result<Vec<Run>> {
+    Ok(Telemetry::all(working_dir)?
+        .into_iter()
+        .map(|t| Run {
+            id: t.id(),
+            manifest: t.read_manifest().ok(),
+        })
+        .collect())
+}
+
+pub fn history_list(filter: HistoryFilter, json: bool, working_dir: &Utf8Path) -> Result<()> {
+    let runs: Vec<Run> = runs(working_dir)?
+        .into_iter()
+        .filter(|r| filter.matches(r))
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&runs)?);
+        return Ok(());
+    }
+
+    if runs.is_empty() {
+        println!("No runs recorded in {working_dir}");
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        format!(
+            "{:<6} {:<18} {:<12} {:<21} {:>10}  {:<7}  {}",
+            "ID", "APP", "ENGINE", "STARTED", "DURATION", "STATUS", "TAGS"
+        )
+        .dim()
+    );
+    for run in &runs {
+        let Some(m) = &run.manifest else {
+            println!("{:04}   {}", run.id, "no manifest".dim());
+            continue;
+        };
+        println!(
+            "{:04}   {:<18} {:<12} {:<21} {:>10}  {:<7}  {}",
+            run.id,
+            m.app.green(),
+            m.engine,
+            m.started,
+            format_duration(m.wall_time_secs),
+            status(m.success),
+            m.tags.join(",")
+        );
+    }
+    Ok(())
+}
+
+/// `rc history show`: a run's details followed by its log, with the command line highlighted.
+pub fn history_show(run: &str, working_dir: &Utf8Path) -> Result<()> {
+    let t = Telemetry::find(working_dir, run)?;
+
+    if let Ok(m) = t.read_manifest() {
+        println!("{} {:04}", "Run:".bold(), t.id());
+        println!("{} {}", "App:".bold(), m.app.green());
+        println!("{} {}", "Engine:".bold(), m.engine);
+        if let Some(image) = &m.image {
+            println!("{} {image}", "Image:".bold());
+        }
+        if let Some(digest) = &m.digest {
+            println!("{} {digest}", "Digest:".bold());
+        }
+        println!(
+            "{} {} ({})",
+            "Started:".bold(),
+            m.started,
+            format_duration(m.wall_time_secs)
+        );
+        println!("{} {}", "Status:".bold(), status(m.success));
+        if !m.tags.is_empty() {
+            println!("{} {}", "Tags:".bold(), m.tags.join(", ").bright_blue());
+        }
+        if let Some(note) = &m.note {
+            println!("{} {note}", "Note:".bold());
+        }
+        println!();
+    }
+
+    let log = t.log_file_name();
+    if !log.exists() {
+        return Ok(());
+    }
+    let content = fs::read_to_string(&log)?;
+    // logs of earlier versions start with the `# rewrote argument` lines
+    let mut lines = content
+        .lines()
+        .skip_while(|l| l.starts_with("# rewrote argument"));
+    if let Some(command) = lines.next() {
+        println!("{} {}", "$".dim(), command.bright_white().bold());
+    }
+    for line in lines {
+        match line.strip_prefix("process success: ") {
+            Some("true") => println!("{}", line.green()),
+            Some(_) => println!("{}", line.bright_red()),
+            None => println!("{line}"),
+        }
+    }
+    Ok(())
+}
+
+fn status(success: Option<bool>) -> yansi::Painted<&'static str> {
+    match success {
+        Some(true) => "success".green(),
+        Some(false) => "failed".bright_red(),
+        None => "unknown".orange(),
+    }
+}
+
+fn format_duration(secs: Option<f64>) -> String {
+    match secs {
+        Some(secs) => {
+            humantime::format_duration(Duration::from_secs(secs.round() as u64)).to_string()
+        }
+        None => "-".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::RunOptions;
+
+    fn run(app: App, success: bool, tags: &[&str]) -> Run {
+        let options = RunOptions {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut manifest =
+            Manifest::new(app.spec(), "docker", &[], &options, Utf8Path::new("/w")).unwrap();
+        manifest.success = Some(success);
+        Run {
+            id: 0,
+            manifest: Some(manifest),
+        }
+    }
+
+    #[test]
+    fn filter_criteria_are_combined() {
+        let ok_rosetta = run(App::Rosetta, true, &["baseline"]);
+        let failed_rosetta = run(App::Rosetta, false, &[]);
+        let ok_foundry = run(App::Foundry, true, &["baseline"]);
+        let legacy = Run {
+            id: 1,
+            manifest: None,
+        };
+
+        let all = HistoryFilter::default();
+        assert!(all.matches(&legacy));
+
+        let rosetta_ok = HistoryFilter {
+            app: Some(App::Rosetta),
+            status: Some(Status::Success),
+            ..Default::default()
+        };
+        assert!(rosetta_ok.matches(&ok_rosetta));
+        assert!(!rosetta_ok.matches(&failed_rosetta));
+        assert!(!rosetta_ok.matches(&ok_foundry));
+        assert!(!rosetta_ok.matches(&legacy));
+
+        let tagged = HistoryFilter {
+            tag: Some("baseline".into()),
+            ..Default::default()
+        };
+        assert!(tagged.matches(&ok_foundry));
+        assert!(!tagged.matches(&failed_rosetta));
+    }
+
+    #[test]
+    fn durations_are_rounded() {
+        assert_eq!(format_duration(Some(3725.4)), "1h 2m 5s");
+        assert_eq!(format_duration(None), "-");
+    }
+}
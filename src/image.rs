@@ -0,0 +1,352 @@
+use std::{collections::BTreeMap, fmt, fs};
+
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use yansi::Paint;
+
+use crate::{
+    app::{App, AppSpec},
+    config,
+    engine::ContainerEngine,
+    util::{Command, yansi::PaintExt},
+};
+
+pub const LOCK_FILE: &str = "rc.lock";
+
+/// Container image reference `[registry/]repository[:tag][@digest]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageRef {
+    pub repository: String,
+    pub tag: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl ImageRef {
+    pub fn parse(image: &str) -> Self {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest.to_string())),
+            None => (image, None),
+        };
+
+        // a `:` after the last `/` separates the tag, earlier ones belong to a registry port
+        let (repository, tag) = match name.rfind(':') {
+            Some(i) if !name[i..].contains('/') => (&name[..i], Some(name[i + 1..].to_string())),
+            _ => (name, None),
+        };
+
+        ImageRef {
+            repository: repository.into(),
+            tag,
+            digest,
+        }
+    }
+
+    /// Reference pinned to `digest`, the tag is dropped since the digest fully identifies the image.
+    pub fn pinned(&self, digest: &str) -> String {
+        format!("{}@{digest}", self.repository)
+    }
+}
+
+impl fmt::Display for ImageRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.repository)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{tag}")?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{digest}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockEntry {
+    /// Image reference the digest was resolved from
+    pub image: String,
+    pub digest: String,
+}
+
+/// `rc.lock`: per-app image digests resolved by `rc lock`, honored by the Docker, Podman and HPC engines.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default)]
+    pub apps: BTreeMap<String, LockEntry>,
+}
+
+impl Lockfile {
+    pub fn path(working_dir: &Utf8Path) -> Utf8PathBuf {
+        working_dir.join(LOCK_FILE)
+    }
+
+    pub fn load(working_dir: &Utf8Path) -> Result<Option<Self>> {
+        let path = Self::path(working_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        let lockfile: Self = toml_edit::de::from_str(&content)
+            .with_context(|| format!("failed to parse lock file {path}"))?;
+        // the digest ends up in image references passed to the engines, only accept real ones
+        for (app, entry) in &lockfile.apps {
+            if !is_digest(&entry.digest) {
+                return Err(anyhow!(
+                    "invalid digest `{}` for {app} in lock file {path}, expected sha256:<64 hex digits>",
+                    entry.digest
+                ));
+            }
+        }
+        Ok(Some(lockfile))
+    }
+
+    pub fn save(&self, working_dir: &Utf8Path) -> Result<()> {
+        let content = format!(
+            "# Generated by `rc lock`, image digests pinned for runs in this directory\n{}",
+            toml_edit::ser::to_string_pretty(self)?
+        );
+        fs::write(Self::path(working_dir), content)?;
+        Ok(())
+    }
+}
+
+/// Whether `digest` is a `sha256:` digest of 64 lowercase hex digits.
+fn is_digest(digest: &str) -> bool {
+    digest.strip_prefix("sha256:").is_some_and(|hex| {
+        hex.len() == 64 && hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    })
+}
+
+/// Image to run `app` with: `--image` override, then an `rc.lock` entry for the configured image,
+/// then the configured image (`apps.<app>.image/tag/digest`).
+pub fn run_image(
+    app: &dyn AppSpec,
+    image_override: Option<&str>,
+    working_dir: &Utf8Path,
+) -> Result<String> {
+    if let Some(image) = image_override {
+        return Ok(image.into());
+    }
+
+    let configured = config::settings().container_image(app);
+    let name = app.name().to_lowercase();
+
+    match Lockfile::load(working_dir)?.and_then(|l| l.apps.get(&name).cloned()) {
+        Some(entry) if entry.image == configured => {
+            let pinned = ImageRef::parse(&entry.image).pinned(&entry.digest);
+            println!(
+                "Using image pinned by {LOCK_FILE}: {}",
+                pinned.bright_blue()
+            );
+            Ok(pinned)
+        }
+        Some(entry) => {
+            eprintln!(
+                "{}",
+                format!(
+                    "{LOCK_FILE} pins {} for {name} but {configured} is configured, ignoring the lock entry (re-run `rc lock {name}` to update it)",
+                    entry.image
+                )
+                .orange()
+            );
+            Ok(configured)
+        }
+        None => Ok(configured),
+    }
+}
+
+/// Resolve `image` to its registry digest (`sha256:...`), using Docker when its daemon is
+/// reachable, then Podman, and `skopeo` otherwise.
+pub fn resolve_digest(image: &str) -> Result<String> {
+    for engine in [ContainerEngine::Docker, ContainerEngine::Podman] {
+        if !engine.is_usable() {
+            continue;
+        }
+        let tool = engine.to_string();
+        Command::new(&tool)
+            .args(["image", "pull", image])
+            .live()
+            .exec()
+            .with_context(|| format!("failed to pull {image}"))?;
+
+        return local_digest(&tool, image)
+            .ok_or_else(|| anyhow!("no registry digest found for {image}"));
+    }
+
+    if which::which("skopeo").is_ok() {
+        let result = Command::new("skopeo")
+            .args(["inspect", "--format", "{{.Digest}}"])
+            .arg(format!("docker://{image}"))
+            .try_call();
+        if result.success && !result.stdout.trim().is_empty() {
+            return Ok(result.stdout.trim().into());
+        }
+        return Err(anyhow!(
+            "failed to inspect {image}: {}",
+            result.stderr.trim()
+        ));
+    }
+
+    Err(anyhow!(
+        "Resolving image digests requires a reachable Docker daemon, Podman or `skopeo` on PATH"
+    ))
+}
+
+/// Registry digest of an `image` pulled by `tool` (`docker` or `podman`), `None` if it is missing
+/// or was never pushed.
+pub fn local_digest(tool: &str, image: &str) -> Option<String> {
+    let result = Command::new(tool)
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{json .RepoDigests}}",
+            image,
+        ])
+        .try_call();
+    if !result.success {
+        return None;
+    }
+
+    let repo_digests: Vec<String> = serde_json::from_str(result.stdout.trim()).ok()?;
+    let repository = ImageRef::parse(image).repository;
+
+    repo_digests
+        .iter()
+        .filter_map(|d| d.split_once('@'))
+        .find(|(repo, _)| *repo == repository || repository.ends_with(&format!("/{repo}")))
+        .or_else(|| repo_digests.first().and_then(|d| d.split_once('@')))
+        .map(|(_, digest)| digest.to_string())
+}
+
+/// Digest of the image `engine` would run for `app` in `working_dir` without pulling, `None` when
+/// it cannot be determined locally.
+pub fn current_digest(
+    app: &dyn AppSpec,
+    engine: ContainerEngine,
+    working_dir: &Utf8Path,
+) -> Option<String> {
+    let image = config::settings().container_image(app);
+    if let Some(digest) = ImageRef::parse(&image).digest {
+        return Some(digest);
+    }
+
+    let name = app.name().to_lowercase();
+    let locked = Lockfile::load(working_dir)
+        .ok()
+        .flatten()
+        .and_then(|l| l.apps.get(&name).cloned())
+        .filter(|entry| entry.image == image);
+
+    match locked {
+        Some(entry) => Some(entry.digest),
+        None if matches!(engine, ContainerEngine::Docker | ContainerEngine::Podman) => {
+            local_digest(&engine.to_string(), &image)
+        }
+        None => None,
+    }
+}
+
+/// `rc lock`: resolve the configured image of each app to a digest and record it in `rc.lock`.
+pub fn lock(apps: Vec<App>, working_dir: &Utf8Path) -> Result<()> {
+    let mut lockfile = Lockfile::load(working_dir)?.unwrap_or_default();
+
+    for app in apps {
+        let image = config::settings().container_image(app.spec());
+        let digest = match ImageRef::parse(&image).digest {
+            Some(digest) => digest,
+            None => resolve_digest(&image)?,
+        };
+
+        println!("{:<20} {image} -> {}", app.green(), digest.bright_blue());
+        lockfile
+            .apps
+            .insert(app.to_string(), LockEntry { image, digest });
+    }
+
+    lockfile.save(working_dir)?;
+    println!("Wrote {}", Lockfile::path(working_dir));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_repository() {
+        let r = ImageRef::parse("rosettacommons/rfdiffusion");
+        assert_eq!(r.repository, "rosettacommons/rfdiffusion");
+        assert_eq!(r.tag, None);
+        assert_eq!(r.digest, None);
+    }
+
+    #[test]
+    fn parse_tag_and_registry_port() {
+        let r = ImageRef::parse("localhost:5000/rosettacommons/rosetta:serial");
+        assert_eq!(r.repository, "localhost:5000/rosettacommons/rosetta");
+        assert_eq!(r.tag.as_deref(), Some("serial"));
+        assert_eq!(
+            r.to_string(),
+            "localhost:5000/rosettacommons/rosetta:serial"
+        );
+    }
+
+    #[test]
+    fn parse_digest() {
+        let r = ImageRef::parse("ghcr.io/sokrypton/colabfold:1.6.0-cuda12@sha256:abc");
+        assert_eq!(r.repository, "ghcr.io/sokrypton/colabfold");
+        assert_eq!(r.tag.as_deref(), Some("1.6.0-cuda12"));
+        assert_eq!(r.digest.as_deref(), Some("sha256:abc"));
+        assert_eq!(
+            r.pinned("sha256:def"),
+            "ghcr.io/sokrypton/colabfold@sha256:def"
+        );
+    }
+
+    #[test]
+    fn lockfile_roundtrip() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+        assert!(Lockfile::load(dir).unwrap().is_none());
+
+        let mut lockfile = Lockfile::default();
+        lockfile.apps.insert(
+            "rosetta".into(),
+            LockEntry {
+                image: "rosettacommons/rosetta:serial".into(),
+                digest: format!("sha256:{}", "0a".repeat(32)),
+            },
+        );
+        lockfile.save(dir).unwrap();
+
+        let loaded = Lockfile::load(dir).unwrap().unwrap();
+        assert_eq!(loaded.apps, lockfile.apps);
+    }
+
+    #[test]
+    fn lockfile_rejects_invalid_digests() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+
+        for digest in [
+            "sha256:x; rm -rf ~",
+            "sha256:abc",
+            "sha512:0123",
+            &format!("sha256:{}", "0A".repeat(32)),
+        ] {
+            let mut lockfile = Lockfile::default();
+            lockfile.apps.insert(
+                "rosetta".into(),
+                LockEntry {
+                    image: "rosettacommons/rosetta:serial".into(),
+                    digest: digest.into(),
+                },
+            );
+            lockfile.save(dir).unwrap();
+            let err = Lockfile::load(dir).unwrap_err().to_string();
+            assert!(err.contains("invalid digest"), "{digest}: {err}");
+        }
+    }
+}
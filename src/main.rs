@@ -1,16 +1,28 @@
 mod app;
+mod bundle;
+mod cache;
+mod catalog;
 mod config;
+mod doctor;
 mod driver;
 mod engine;
+mod history;
+mod image;
+mod slurm;
 mod telemetry;
 mod util;
 
 use anyhow::{Result, anyhow};
 use camino::Utf8PathBuf;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use strum::IntoEnumIterator;
 use yansi::Paint;
 
-use crate::{app::App, config::config_show, driver::install, engine::ContainerEngine};
+use crate::{
+    app::App,
+    driver::install,
+    engine::{ContainerEngine, Gpus, Limits, Mount, RunOptions},
+};
 
 /// A command line tool to run various Rosetta applications
 #[derive(Parser, Debug)]
@@ -50,32 +62,165 @@ enum Commands {
         #[arg(value_enum)]
         app: App,
 
+        /// Container engine, defaults to `engine.default` from the configuration
         #[arg(short = 'e', long)]
-        container_engine: ContainerEngine,
+        container_engine: Option<ContainerEngine>,
+
+        /// Install into the site-wide `shared.root` with group-readable permissions, for admins
+        #[arg(long)]
+        shared: bool,
+    },
+
+    /// Re-pull images and rebuild outdated native environments, then report which apps changed
+    Update {
+        /// The app to update (default: every app)
+        #[arg(value_enum)]
+        app: Option<App>,
+
+        /// Container engine, defaults to `engine.default` from the configuration
+        #[arg(short = 'e', long)]
+        container_engine: Option<ContainerEngine>,
     },
 
     /// Run an app with optional arguments
-    Run {
-        /// The app to run
+    Run(RunArgs),
+
+    /// Submit a run as a Slurm batch job wrapping `rc run`
+    Submit {
+        #[command(flatten)]
+        slurm: slurm::SlurmArgs,
+
+        #[command(flatten)]
+        run: RunArgs,
+    },
+
+    /// List and inspect previous runs in a working directory
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        history_command: Option<HistoryCmd>,
+
+        #[command(flatten)]
+        list: HistoryListArgs,
+    },
+
+    /// Re-execute a previous run from its manifest
+    Rerun {
+        /// Run id (e.g. `3`), `last`, or the path of a `.NNNN.rc.log`/`.NNNN.rc.json` file
+        run: String,
+
+        /// Directory the run was recorded in (default: current directory)
+        #[arg(short, long)]
+        working_dir: Option<Utf8PathBuf>,
+
+        /// Container engine to use instead of the one the run was recorded with
+        #[arg(short = 'e', long)]
+        container_engine: Option<ContainerEngine>,
+    },
+
+    /// List every app with its description and install status per engine
+    Apps {
+        /// Output as JSON (useful for scripting)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show an app's homepage, image, expected arguments and install status
+    Info {
+        /// The app to describe
         #[arg(value_enum)]
         app: App,
 
-        /// Optional arguments for the app
-        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
-        args: Vec<String>,
+        /// Output as JSON (useful for scripting)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Resolve app images to digests and pin them in `rc.lock` in the working directory
+    Lock {
+        /// The apps to lock
+        #[arg(value_enum, required_unless_present = "all", conflicts_with = "all")]
+        apps: Vec<App>,
 
-        /// Input directory path
+        /// Lock every app
+        #[arg(short, long)]
+        all: bool,
+
+        /// Directory to write `rc.lock` into (default: current directory)
         #[arg(short, long)]
         working_dir: Option<Utf8PathBuf>,
+    },
 
-        #[arg(short = 'e', long, default_value = "docker")]
-        container_engine: ContainerEngine,
+    /// Move images and native environments to hosts without internet access as tar bundles
+    Bundle {
+        #[command(subcommand)]
+        bundle_command: BundleCmd,
+    },
+
+    /// Inspect and prune cached images and native environments
+    Cache {
+        #[command(subcommand)]
+        cache_command: CacheCmd,
     },
 
+    /// Inspect and modify the rc configuration
     Config {
         #[command(subcommand)]
         config_command: ConfigCmd,
     },
+
+    /// Check container engines, cache and installed apps, printing fixes for problems found
+    Doctor {
+        /// Output as JSON (useful for scripting)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a shell completion script, e.g. `rc completions bash > ~/.local/share/bash-completion/completions/rc`
+    Completions {
+        /// Shell to generate the completion script for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page, or write one page per command into a directory
+    Man {
+        /// Directory to write `rc.1` and the subcommand pages into
+        #[arg(short, long)]
+        out_dir: Option<Utf8PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleCmd {
+    /// Package the images or native environments of apps, installing them first when needed
+    Export {
+        /// The apps to export
+        #[arg(value_enum, required_unless_present = "all", conflicts_with = "all")]
+        apps: Vec<App>,
+
+        /// Export every app the engine supports
+        #[arg(short, long)]
+        all: bool,
+
+        /// Container engine to export for (`none` exports Pixi environments)
+        #[arg(short = 'e', long)]
+        container_engine: ContainerEngine,
+
+        /// Bundle file to write
+        #[arg(short, long)]
+        output: Utf8PathBuf,
+
+        /// Directory whose `rc.lock` pins the images to export (default: current directory)
+        #[arg(short, long)]
+        working_dir: Option<Utf8PathBuf>,
+    },
+
+    /// Install the images or environments of a bundle after verifying their checksums
+    Import {
+        /// Bundle file written by `rc bundle export`
+        bundle: Utf8PathBuf,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -92,26 +237,191 @@ enum ConfigCmd {
         #[arg(long)]
         json: bool,
     },
-    // /// Set a configuration value
-    // Set {
-    //     /// Dotted key path, e.g. `cache.root`
-    //     key: String,
+    /// Set a configuration value in the user config file
+    Set {
+        /// Dotted key path, e.g. `cache.root`
+        key: String,
+
+        /// Value to set
+        value: String,
+    },
+
+    /// Remove a configuration override (fall back to defaults)
+    Unset {
+        /// Dotted key path, e.g. `cache.root`
+        key: String,
+    },
+
+    /// Open the config file in $EDITOR
+    Edit,
+
+    /// Print the config file path
+    Path,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCmd {
+    /// List cached artifacts with their size, owning app and last use
+    Ls {
+        /// Output as JSON (useful for scripting)
+        #[arg(long)]
+        json: bool,
+    },
 
-    //     /// Value to set (stringly-typed; you parse/validate per key)
-    //     value: String,
-    // },
+    /// Show disk usage of the cache
+    Du {
+        /// Output as JSON (useful for scripting)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Remove cached artifacts matching all of the given filters
+    #[command(group(clap::ArgGroup::new("filter").required(true).multiple(true)))]
+    Prune {
+        /// Only artifacts not used for this long, e.g. `30d`, `2weeks`
+        #[arg(long, value_parser = humantime::parse_duration, group = "filter")]
+        older_than: Option<std::time::Duration>,
+
+        /// Only artifacts of these apps
+        #[arg(long = "app", value_enum, group = "filter")]
+        apps: Vec<App>,
 
-    // /// Remove a configuration override (fall back to defaults)
-    // Unset {
-    //     /// Dotted key path, e.g. `cache.root`
-    //     key: String,
-    // },
+        /// Only images/environments not referenced by any app of this rc version
+        #[arg(long, group = "filter")]
+        stale: bool,
+
+        /// Prune everything in the cache
+        #[arg(long, group = "filter", conflicts_with_all = ["older_than", "apps", "stale"])]
+        all: bool,
+
+        /// Only print what would be removed
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+}
 
-    // /// Open the config file in $EDITOR
-    // Edit,
+#[derive(Subcommand, Debug)]
+enum HistoryCmd {
+    /// Print a run's details, command line and output
+    Show {
+        /// Run id (e.g. `3`), `last`, or the path of a `.NNNN.rc.log`/`.NNNN.rc.json` file
+        run: String,
 
-    // /// Print the config file path
-    // Path,
+        /// Directory the run was recorded in (default: current directory)
+        #[arg(short, long)]
+        working_dir: Option<Utf8PathBuf>,
+    },
+}
+
+/// Options of `rc run`, shared with `rc submit`.
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    /// The app to run
+    #[arg(value_enum)]
+    app: App,
+
+    /// Optional arguments for the app
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+    args: Vec<String>,
+
+    /// Input directory path
+    #[arg(short, long)]
+    working_dir: Option<Utf8PathBuf>,
+
+    /// Container engine, defaults to `engine.default` from the configuration
+    #[arg(short = 'e', long)]
+    container_engine: Option<ContainerEngine>,
+
+    /// Container image to use instead of the configured (or `rc.lock` pinned) one
+    #[arg(long)]
+    image: Option<String>,
+
+    /// Label the run, can be repeated (see `rc history --tag`)
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+
+    /// Free-form note stored with the run
+    #[arg(long)]
+    note: Option<String>,
+
+    /// GPUs to pass through: `all`, `none`, a count or `device=0,2`. GPU apps get `all`
+    /// when a GPU is detected
+    #[arg(long, value_name = "GPUS")]
+    gpus: Option<Gpus>,
+
+    #[command(flatten)]
+    limits: Limits,
+
+    /// Extra bind mount `host[:container][:ro]`, can be repeated. Without a container path
+    /// the host path is mounted at the same location
+    #[arg(long = "mount", value_name = "MOUNT", value_parser = parse_mount)]
+    mounts: Vec<Mount>,
+
+    /// Environment variable `KEY=VALUE` for the app, can be repeated. A bare `KEY` passes its
+    /// current value
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = engine::parse_env_var)]
+    env: Vec<(String, String)>,
+
+    /// Read environment variables from a file of `KEY=VALUE` lines, can be repeated
+    #[arg(long = "env-file", value_name = "PATH")]
+    env_files: Vec<Utf8PathBuf>,
+
+    /// Pass absolute host paths in the app arguments through unchanged instead of mounting
+    /// their directories read-only and rewriting them to container paths
+    #[arg(long)]
+    no_auto_mount: bool,
+
+    /// Record the run under this run id, reserved by `rc submit` for its batch job
+    #[arg(long, hide = true)]
+    run_id: Option<u32>,
+}
+
+impl RunArgs {
+    fn options(&self) -> Result<RunOptions> {
+        Ok(RunOptions {
+            env: self
+                .env_files
+                .iter()
+                .map(|path| engine::read_env_file(path))
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .chain(self.env.iter().cloned())
+                .collect(),
+            image: self.image.clone(),
+            tags: self.tags.clone(),
+            note: self.note.clone(),
+            gpus: self.gpus.clone(),
+            limits: self.limits.clone(),
+            mounts: self.mounts.clone(),
+            no_auto_mount: self.no_auto_mount,
+            run_id: self.run_id,
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct HistoryListArgs {
+    /// Only runs of this app
+    #[arg(long, value_enum)]
+    app: Option<App>,
+
+    /// Only successful or failed runs
+    #[arg(long, value_enum)]
+    status: Option<history::Status>,
+
+    /// Only runs labelled with this tag
+    #[arg(long)]
+    tag: Option<String>,
+
+    /// Output as JSON (useful for scripting)
+    #[arg(long)]
+    json: bool,
+
+    /// Directory to list runs of (default: current directory)
+    #[arg(short, long)]
+    working_dir: Option<Utf8PathBuf>,
 }
 
 #[derive(clap::Args, Debug)]
@@ -119,13 +429,14 @@ struct ConfigShowArgs {
     /// Output as JSON (useful for scripting)
     #[arg(long)]
     json: bool,
-    // /// Output as TOML (optional; pick what you support)
-    // #[arg(long, conflicts_with = "json")]
-    // toml: bool,
 
-    // /// Include where each value came from (defaults/env/file)
-    // #[arg(long)]
-    // origin: bool,
+    /// Include where each value came from (default/file/env)
+    #[arg(long)]
+    origin: bool,
+
+    /// Directory used to discover the project file (default: current directory)
+    #[arg(short, long)]
+    working_dir: Option<Utf8PathBuf>,
 }
 
 fn main() -> Result<()> {
@@ -136,6 +447,13 @@ fn main() -> Result<()> {
         println!("Args: {:#?}", args);
     }
 
+    if !matches!(
+        args.command,
+        Commands::Config { .. } | Commands::Completions { .. } | Commands::Man { .. }
+    ) {
+        config::load()?;
+    }
+
     match args.command {
         Commands::Clean {
             app,
@@ -146,32 +464,139 @@ fn main() -> Result<()> {
         Commands::Install {
             app,
             container_engine,
-        } => install(app, container_engine),
+            shared,
+        } => install(
+            app,
+            container_engine.unwrap_or_else(|| config::settings().engine()),
+            shared,
+        ),
 
-        Commands::Run {
+        Commands::Update {
             app,
-            args: app_args,
             container_engine,
-            working_dir,
-        } => {
-            let working_dir = working_dir
-                .unwrap_or_else(|| Utf8PathBuf::from("."))
-                .canonicalize()
-                .map_err(|_| anyhow!("Specified working directory does not exist".red()))?;
-
-            let working_dir = Utf8PathBuf::try_from(working_dir)
-                .map_err(|_| anyhow!("Working dir path contains invalid UTF-8".red()))?;
+        } => driver::update(app, container_engine),
 
-            driver::run(app.spec(), app_args, container_engine, working_dir)
+        Commands::Run(run) => {
+            let options = run.options()?;
+            driver::run(
+                run.app.spec(),
+                run.args,
+                run.container_engine,
+                options,
+                resolve_working_dir(run.working_dir)?,
+            )
+        }
+        Commands::Submit { slurm, run } => {
+            let options = run.options()?;
+            slurm::submit(
+                run.app,
+                run.args,
+                run.container_engine,
+                options,
+                &slurm,
+                resolve_working_dir(run.working_dir)?,
+            )
         }
+        Commands::History {
+            history_command,
+            list,
+        } => match history_command {
+            Some(HistoryCmd::Show { run, working_dir }) => {
+                history::history_show(&run, &resolve_working_dir(working_dir)?)
+            }
+            None => history::history_list(
+                history::HistoryFilter {
+                    app: list.app,
+                    status: list.status,
+                    tag: list.tag,
+                },
+                list.json,
+                &resolve_working_dir(list.working_dir)?,
+            ),
+        },
+        Commands::Rerun {
+            run,
+            working_dir,
+            container_engine,
+        } => driver::rerun(&run, container_engine, resolve_working_dir(working_dir)?),
+        Commands::Apps { json } => catalog::apps(json),
+        Commands::Info { app, json } => catalog::info(app, json),
+        Commands::Lock {
+            apps,
+            all,
+            working_dir,
+        } => image::lock(
+            if all { App::iter().collect() } else { apps },
+            &resolve_working_dir(working_dir)?,
+        ),
+        Commands::Bundle { bundle_command } => match bundle_command {
+            BundleCmd::Export {
+                apps,
+                all,
+                container_engine,
+                output,
+                working_dir,
+            } => bundle::export(
+                if all {
+                    App::iter()
+                        .filter(|a| {
+                            container_engine != ContainerEngine::None
+                                || a.spec().pixi_recipe().is_some()
+                        })
+                        .collect()
+                } else {
+                    apps
+                },
+                container_engine,
+                &output,
+                &resolve_working_dir(working_dir)?,
+            ),
+            BundleCmd::Import { bundle } => bundle::import(&bundle),
+        },
+        Commands::Cache { cache_command } => match cache_command {
+            CacheCmd::Ls { json } => cache::cache_ls(json),
+            CacheCmd::Du { json } => cache::cache_du(json),
+            CacheCmd::Prune {
+                older_than,
+                apps,
+                stale,
+                all: _,
+                dry_run,
+            } => cache::cache_prune(
+                cache::PruneFilter {
+                    older_than,
+                    apps,
+                    stale,
+                },
+                dry_run,
+            ),
+        },
         Commands::Config { config_command } => match config_command {
-            ConfigCmd::Show(show_args) => config_show(show_args.json),
-            ConfigCmd::Get { .. } => unimplemented!(),
-            // ConfigCmd::Set(_) => unimplemented!(),
-            // ConfigCmd::Unset(_) => unimplemented!(),
-            // ConfigCmd::Edit => unimplemented!(),
-            // ConfigCmd::Path => unimplemented!(),
+            ConfigCmd::Show(show_args) => config::config_show(
+                show_args.json,
+                show_args.origin,
+                show_args
+                    .working_dir
+                    .as_deref()
+                    .unwrap_or(camino::Utf8Path::new(".")),
+            ),
+            ConfigCmd::Get { key, json } => config::config_get(&key, json),
+            ConfigCmd::Set { key, value } => config::config_set(&key, &value),
+            ConfigCmd::Unset { key } => config::config_unset(&key),
+            ConfigCmd::Edit => config::config_edit(),
+            ConfigCmd::Path => config::config_path(),
         },
+        Commands::Doctor { json } => doctor::doctor(json),
+        Commands::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Args::command(),
+                env!("CARGO_PKG_NAME"),
+                &mut std::io::stdout(),
+            );
+            Ok(())
+        }
+        Commands::Man { out_dir } => man(out_dir),
         // None => {
         //     eprintln!("Error: No command specified");
         //     eprintln!("Use --help to see available commands");
@@ -179,3 +604,37 @@ fn main() -> Result<()> {
         // }
     }
 }
+
+fn man(out_dir: Option<Utf8PathBuf>) -> Result<()> {
+    let command = Args::command();
+    match out_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(command, &dir)?;
+            println!("Man pages written to {}", dir.bright_blue());
+        }
+        None => clap_mangen::Man::new(command).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
+
+/// Parse a `--mount` value, relative host paths are taken from the current directory and must
+/// exist (Docker would otherwise create them owned by root).
+fn parse_mount(spec: &str) -> Result<Mount> {
+    let current_dir = Utf8PathBuf::try_from(std::env::current_dir()?)?;
+    let mount = Mount::parse(spec, &current_dir)?;
+    if !mount.host.exists() {
+        return Err(anyhow!("mount source {} does not exist", mount.host));
+    }
+    Ok(mount)
+}
+
+fn resolve_working_dir(working_dir: Option<Utf8PathBuf>) -> Result<Utf8PathBuf> {
+    let working_dir = working_dir
+        .unwrap_or_else(|| Utf8PathBuf::from("."))
+        .canonicalize()
+        .map_err(|_| anyhow!("Specified working directory does not exist".red()))?;
+
+    Utf8PathBuf::try_from(working_dir)
+        .map_err(|_| anyhow!("Working dir path contains invalid UTF-8".red()))
+}
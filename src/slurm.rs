@@ -0,0 +1,360 @@
+use std::fs;
+
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use yansi::Paint;
+
+use crate::{
+    app::App,
+    config::{self, Project},
+    engine::{ContainerEngine, Gpus, Limits, RunOptions},
+    image,
+    telemetry::{Telemetry, is_secret},
+    util::Command,
+};
+
+/// Placeholder in app arguments replaced by the array task id of each job.
+const TASK_PLACEHOLDER: &str = "{task}";
+
+/// Slurm resources of `rc submit`, the CPU, memory and GPU requests come from the run options.
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct SlurmArgs {
+    /// Partition to submit to, defaults to `slurm.partition` from the configuration
+    #[arg(short, long)]
+    pub partition: Option<String>,
+
+    /// Wall time limit, e.g. `2:00:00` or `1-12:00:00`, defaults to `slurm.time`
+    #[arg(short, long)]
+    pub time: Option<String>,
+
+    /// Account charged for the job, defaults to `slurm.account`
+    #[arg(short = 'A', long)]
+    pub account: Option<String>,
+
+    /// Job array index range, e.g. `1-100` or `0-99%10`. `{task}` in the app arguments is
+    /// replaced by the index of each task
+    #[arg(long, value_name = "RANGE")]
+    pub array: Option<String>,
+
+    /// Job name, defaults to `rc-<app>`
+    #[arg(short = 'J', long)]
+    pub job_name: Option<String>,
+
+    /// Print the generated batch script instead of submitting it
+    #[arg(long)]
+    pub print: bool,
+}
+
+/// Submit a run of `app` as a Slurm batch job executing `rc run`. The image or environment is
+/// prepared first so array tasks do not race to build it, and the script is kept in
+/// `working_dir` as `.NNNN.rc.sbatch`.
+pub fn submit(
+    app: App,
+    args: Vec<String>,
+    engine: Option<ContainerEngine>,
+    mut options: RunOptions,
+    slurm: &SlurmArgs,
+    working_dir: Utf8PathBuf,
+) -> Result<()> {
+    let spec = app.spec();
+    let project = Project::discover(&working_dir)?;
+    let engine = engine
+        .unwrap_or_else(|| config::settings().engine_for(spec, project.as_ref()))
+        .resolve(spec)?;
+
+    if !slurm.print && which::which("sbatch").is_err() {
+        return Err(anyhow!(
+            "`sbatch` not found, `rc submit` needs a Slurm login node"
+        ));
+    }
+
+    // a single job records its run under the id of the script, array tasks take their own ids
+    let t = match slurm.print {
+        true => Telemetry::next(&working_dir),
+        false => Telemetry::new(&working_dir)?,
+    };
+    let run_id = (!slurm.print && slurm.array.is_none()).then_some(t.id());
+    let result = (|| -> Result<()> {
+        let rc = std::env::current_exe()?;
+        let rc = Utf8Path::from_path(&rc).ok_or_else(|| anyhow!("rc path is not valid UTF-8"))?;
+        let script = script(
+            &Job {
+                app,
+                args: &args,
+                engine,
+                options: &options,
+                run_id,
+                slurm,
+                working_dir: &working_dir,
+                output: &t.sbatch_file_name().with_extension("out"),
+            },
+            rc,
+        );
+
+        if slurm.print {
+            print!("{script}");
+            return Ok(());
+        }
+
+        if engine != ContainerEngine::None {
+            options.image = Some(image::run_image(
+                spec,
+                options.image.as_deref(),
+                &working_dir,
+            )?);
+        }
+        println!(
+            "Preparing {} for {engine} before submitting...",
+            app.green()
+        );
+        engine.engine().prepare(spec, &options)?;
+
+        let script_path = t.sbatch_file_name();
+        fs::write(&script_path, &script)
+            .with_context(|| format!("failed to write batch script {script_path}"))?;
+
+        // secrets are not written into the script, the job inherits them from this environment
+        let secrets: Vec<_> = options.env.iter().filter(|(k, _)| is_secret(k)).collect();
+        Command::new("sbatch")
+            .args((!secrets.is_empty()).then_some("--export=ALL"))
+            .envs(secrets)
+            .arg(script_path.as_str())
+            .live()
+            .exec()
+            .with_context(|| format!("failed to submit {script_path}"))?;
+        println!(
+            "{}",
+            format!("Batch script saved into {script_path:?}")
+                .blue()
+                .dim()
+        );
+        Ok(())
+    })();
+
+    // the empty log only reserved the id, the script holds it from now on
+    if !slurm.print && (run_id.is_none() || result.is_err()) {
+        let _ = fs::remove_file(t.log_file_name());
+    }
+    result
+}
+
+/// Everything the batch script is generated from.
+struct Job<'a> {
+    app: App,
+    args: &'a [String],
+    engine: ContainerEngine,
+    options: &'a RunOptions,
+    /// Run id reserved for the job's `rc run`, `None` for job arrays
+    run_id: Option<u32>,
+    slurm: &'a SlurmArgs,
+    working_dir: &'a Utf8Path,
+    /// Job output file, `%j` or `%A_%a` is inserted before its extension
+    output: &'a Utf8Path,
+}
+
+/// Batch script running `rc` (the path of this executable) for `job`.
+fn script(job: &Job<'_>, rc: &Utf8Path) -> String {
+    let settings = config::settings();
+    let setting = |key: &str| settings.get(key).map(|s| s.value.clone());
+    let options = job.options;
+
+    let mut directives = vec![
+        format!(
+            "--job-name={}",
+            job.slurm
+                .job_name
+                .clone()
+                .unwrap_or(format!("rc-{}", job.app))
+        ),
+        format!("--chdir={}", job.working_dir),
+        format!(
+            "--output={}",
+            job.output.with_extension(match job.slurm.array {
+                Some(_) => "%A_%a.out",
+                None => "%j.out",
+            })
+        ),
+    ];
+    let optional = [
+        (
+            "partition",
+            job.slurm.partition.clone().or(setting("slurm.partition")),
+        ),
+        ("time", job.slurm.time.clone().or(setting("slurm.time"))),
+        (
+            "account",
+            job.slurm.account.clone().or(setting("slurm.account")),
+        ),
+        ("array", job.slurm.array.clone()),
+        (
+            "cpus-per-task",
+            options.limits.threads().map(|t| t.to_string()),
+        ),
+        ("mem", options.limits.memory.as_deref().map(megabytes)),
+        (
+            "gpus",
+            slurm_gpus(job.app, options.gpus.as_ref()).map(|n| n.to_string()),
+        ),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            directives.push(format!("--{name}={value}"));
+        }
+    }
+
+    let mut command = vec![
+        quote(rc.as_str()),
+        "run".into(),
+        "-e".into(),
+        job.engine.to_string(),
+        "-w".into(),
+        quote(job.working_dir.as_str()),
+    ];
+    command.extend(run_options(job).iter().map(|o| quote(o)));
+    command.push(job.app.to_string());
+    command.extend(job.args.iter().map(|a| quote(a)));
+
+    let mut script = String::from("#!/bin/bash\n");
+    for directive in directives {
+        script += &format!("#SBATCH {directive}\n");
+    }
+    script += &format!("\n# generated by rc {}\n", env!("CARGO_PKG_VERSION"));
+    script += &format!("{}\n", command.join(" "));
+    script
+}
+
+/// `rc run` options repeating `job.options`. GPUs are allocated by Slurm, so the run gets all of
+/// the job's GPUs. Secret environment variables are passed by name only, their values reach the
+/// job through the environment `sbatch` exports.
+fn run_options(job: &Job<'_>) -> Vec<String> {
+    let options = job.options;
+    let mut args = Vec::new();
+    let mut option = |name: &str, value: String| {
+        args.push(format!("--{name}"));
+        args.push(value);
+    };
+
+    if let Some(image) = &options.image {
+        option("image", image.clone());
+    }
+    for tag in &options.tags {
+        option("tag", tag.clone());
+    }
+    if let Some(note) = &options.note {
+        option("note", note.clone());
+    }
+    match slurm_gpus(job.app, options.gpus.as_ref()) {
+        Some(_) => option("gpus", Gpus::All.to_string()),
+        None if options.gpus == Some(Gpus::None) => option("gpus", Gpus::None.to_string()),
+        None => {}
+    }
+    let Limits {
+        cpus,
+        memory,
+        shm_size,
+    } = &options.limits;
+    if let Some(cpus) = cpus {
+        option("cpus", cpus.to_string());
+    }
+    if let Some(memory) = memory {
+        option("memory", memory.clone());
+    }
+    if let Some(shm_size) = shm_size {
+        option("shm-size", shm_size.clone());
+    }
+    for mount in &options.mounts {
+        option("mount", mount.to_string());
+    }
+    for (key, value) in &options.env {
+        match is_secret(key) {
+            true => option("env", key.clone()),
+            false => option("env", format!("{key}={value}")),
+        }
+    }
+    if options.no_auto_mount {
+        args.push("--no-auto-mount".into());
+    }
+    if let Some(id) = job.run_id {
+        args.push("--run-id".into());
+        args.push(id.to_string());
+    }
+    args
+}
+
+/// Number of GPUs to request from Slurm: the `--gpus` count or device count, one for `all` and,
+/// when `--gpus` is not given, one for GPU apps.
+fn slurm_gpus(app: App, gpus: Option<&Gpus>) -> Option<usize> {
+    match gpus {
+        Some(Gpus::None) => None,
+        Some(Gpus::All) => Some(1),
+        Some(Gpus::Count(n)) => Some(*n as usize),
+        Some(Gpus::Devices(devices)) => Some(devices.len()),
+        None => app.spec().wants_gpu().then_some(1),
+    }
+}
+
+/// Docker style size (`16g`, `512m`) as a Slurm memory request in megabytes.
+fn megabytes(size: &str) -> String {
+    let lower = size.to_lowercase();
+    let unit_start = lower
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(lower.len());
+    let number: f64 = lower[..unit_start].parse().unwrap_or_default();
+    let unit = lower[unit_start..]
+        .trim_end_matches("ib")
+        .trim_end_matches('b');
+    let scale = match unit {
+        "k" => 1.0 / 1024.0,
+        "m" => 1.0,
+        "g" => 1024.0,
+        "t" => 1024.0 * 1024.0,
+        _ => 1.0 / (1024.0 * 1024.0),
+    };
+    format!("{}M", (number * scale).ceil() as u64)
+}
+
+/// Quote `arg` for the shell, with `{task}` expanding to the array task id.
+fn quote(arg: &str) -> String {
+    if !arg.contains(TASK_PLACEHOLDER) {
+        return shell_escape::escape(arg.into()).into();
+    }
+    arg.split(TASK_PLACEHOLDER)
+        .map(|part| match part {
+            "" => String::new(),
+            part => shell_escape::escape(part.into()).into(),
+        })
+        .collect::<Vec<_>>()
+        .join("\"$SLURM_ARRAY_TASK_ID\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_converted_to_megabytes() {
+        assert_eq!(megabytes("16g"), "16384M");
+        assert_eq!(megabytes("512m"), "512M");
+        assert_eq!(megabytes("1.5GB"), "1536M");
+        assert_eq!(megabytes("100k"), "1M");
+    }
+
+    #[test]
+    fn task_placeholder_expands_in_quoted_arguments() {
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("in_{task}.pdb"), "in_\"$SLURM_ARRAY_TASK_ID\".pdb");
+        assert_eq!(quote("my dir/{task}"), "'my dir/'\"$SLURM_ARRAY_TASK_ID\"");
+    }
+
+    #[test]
+    fn gpu_requests() {
+        assert_eq!(slurm_gpus(App::Rosetta, None), None);
+        assert_eq!(slurm_gpus(App::Rfdiffusion, None), Some(1));
+        assert_eq!(slurm_gpus(App::Rfdiffusion, Some(&Gpus::None)), None);
+        assert_eq!(slurm_gpus(App::Rosetta, Some(&Gpus::Count(2))), Some(2));
+        let devices = Gpus::Devices(vec!["0".into(), "1".into()]);
+        assert_eq!(slurm_gpus(App::Rosetta, Some(&devices)), Some(2));
+    }
+}
@@ -1,23 +1,68 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{Context, Result, anyhow};
 use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::AppSpec,
+    engine::{Limits, Rewrite, RunOptions},
+    image::ImageRef,
+    util::{self, CommandResults},
+};
 
 pub struct Telemetry {
     working_dir: Utf8PathBuf,
-    prefix: String,
+    id: u32,
 }
 
 impl Telemetry {
-    pub fn new(working_dir: &Utf8Path) -> Self {
-        let mut i: u32 = 0;
+    /// Reserve the next free run id in `working_dir` by creating its (empty) log file, so
+    /// concurrent runs in the same directory, e.g. the tasks of a job array, never share an id.
+    pub fn new(working_dir: &Utf8Path) -> Result<Self> {
+        let mut t = Telemetry::next(working_dir);
         loop {
-            let prefix = format!(".{i:04}.rc");
-            i += 1;
+            let created = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(t.log_file_name());
+            match created {
+                Ok(_) => return Ok(t),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    t = Telemetry::open(working_dir, t.id + 1);
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("failed to create {}", t.log_file_name()));
+                }
+            }
+        }
+    }
 
-            let r = Telemetry {
-                working_dir: working_dir.to_path_buf(),
-                prefix,
-            };
+    /// Records of a run: the id `rc submit` reserved for it or, without one, a new id.
+    pub fn for_run(working_dir: &Utf8Path, run_id: Option<u32>) -> Result<Self> {
+        match run_id {
+            Some(id) => Ok(Telemetry::open(working_dir, id)),
+            None => Telemetry::new(working_dir),
+        }
+    }
+
+    /// First run id without any records in `working_dir`, not reserved.
+    pub fn next(working_dir: &Utf8Path) -> Self {
+        let mut id: u32 = 0;
+        loop {
+            let r = Telemetry::open(working_dir, id);
+            id += 1;
 
-            if r.log_file_name().exists() || r.scratch_dir().exists() {
+            if r.log_file_name().exists()
+                || r.manifest_file_name().exists()
+                || r.scratch_dir().exists()
+                || r.sbatch_file_name().exists()
+            {
                 continue;
             }
 
@@ -25,11 +70,427 @@ impl Telemetry {
         }
     }
 
+    /// Records of the existing run `id` in `working_dir`.
+    pub fn open(working_dir: &Utf8Path, id: u32) -> Self {
+        Telemetry {
+            working_dir: working_dir.to_path_buf(),
+            id,
+        }
+    }
+
+    /// Locate a previous run from a reference: a run id (`3`, `0003`), `last`, or the path of
+    /// its `.NNNN.rc.log`/`.NNNN.rc.json` file. Ids are looked up in `working_dir`.
+    pub fn find(working_dir: &Utf8Path, run: &str) -> Result<Self> {
+        let path = Utf8Path::new(run);
+        if path.is_file() {
+            let id = path
+                .file_name()
+                .and_then(|name| name.strip_prefix('.'))
+                .and_then(|name| name.split_once(".rc."))
+                .and_then(|(id, _)| id.parse().ok())
+                .ok_or_else(|| anyhow!("`{run}` is not an rc run log or manifest"))?;
+            let dir = path.parent().filter(|d| !d.as_str().is_empty());
+            let dir = dir.unwrap_or(Utf8Path::new(".")).canonicalize_utf8()?;
+            return Ok(Telemetry::open(&dir, id));
+        }
+
+        let id = if run == "last" {
+            Telemetry::next(working_dir)
+                .id
+                .checked_sub(1)
+                .ok_or_else(|| anyhow!("no runs recorded in {working_dir}"))?
+        } else {
+            run.parse().map_err(|_| {
+                anyhow!("invalid run `{run}`, expected a run id, `last` or a log file")
+            })?
+        };
+
+        let t = Telemetry::open(working_dir, id);
+        if !t.log_file_name().exists() && !t.manifest_file_name().exists() {
+            return Err(anyhow!("run {id} not found in {working_dir}"));
+        }
+        Ok(t)
+    }
+
+    /// Every run recorded in `working_dir`, ordered by id.
+    pub fn all(working_dir: &Utf8Path) -> Result<Vec<Self>> {
+        let mut ids = Vec::new();
+        for entry in working_dir.read_dir_utf8()? {
+            let entry = entry?;
+            let id = entry
+                .file_name()
+                .strip_prefix('.')
+                .and_then(|name| {
+                    name.strip_suffix(".rc.log")
+                        .or(name.strip_suffix(".rc.json"))
+                })
+                .and_then(|id| id.parse().ok());
+            if let Some(id) = id {
+                ids.push(id);
+            }
+        }
+        ids.sort();
+        ids.dedup();
+        Ok(ids
+            .into_iter()
+            .map(|id| Telemetry::open(working_dir, id))
+            .collect())
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    fn prefix(&self) -> String {
+        format!(".{:04}.rc", self.id)
+    }
+
     pub fn log_file_name(&self) -> Utf8PathBuf {
-        self.working_dir.join(format!("{}.log", self.prefix))
+        self.working_dir.join(format!("{}.log", self.prefix()))
+    }
+
+    /// JSON [`Manifest`] describing the run, written next to the log.
+    pub fn manifest_file_name(&self) -> Utf8PathBuf {
+        self.working_dir.join(format!("{}.json", self.prefix()))
+    }
+
+    /// Slurm batch script generated by `rc submit`, the job's output goes next to it.
+    pub fn sbatch_file_name(&self) -> Utf8PathBuf {
+        self.working_dir.join(format!("{}.sbatch", self.prefix()))
     }
 
     pub fn scratch_dir(&self) -> Utf8PathBuf {
-        self.working_dir.join(format!("rc.scratch/{}", self.prefix))
+        self.working_dir
+            .join(format!("rc.scratch/{}", self.prefix()))
+    }
+
+    pub fn write_manifest(&self, manifest: &Manifest) -> Result<()> {
+        fs::write(
+            self.manifest_file_name(),
+            serde_json::to_string_pretty(manifest)? + "\n",
+        )?;
+        Ok(())
+    }
+
+    pub fn read_manifest(&self) -> Result<Manifest> {
+        let path = self.manifest_file_name();
+        if !path.exists() {
+            return Err(anyhow!(
+                "run {} has no manifest ({path}), it was recorded by an older rc version",
+                self.id
+            ));
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .with_context(|| format!("failed to parse run manifest {path}"))
+    }
+}
+
+/// Machine readable record of a single run, enough to reproduce it later.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub rc_version: String,
+    pub app: String,
+    pub engine: String,
+    pub image: Option<String>,
+    /// Registry digest of `image`, when known
+    pub digest: Option<String>,
+    /// blake3 hash of the Pixi recipe for native runs
+    pub pixi_recipe_hash: Option<String>,
+    /// Existing installation a native run used instead of a Pixi environment
+    #[serde(default)]
+    pub native_prefix: Option<Utf8PathBuf>,
+    /// Arguments as given on the command line
+    pub user_args: Vec<String>,
+    /// Arguments after the app spec rewrote them for the engine
+    pub args: Vec<String>,
+    /// Bind mounts as `host:container[:ro]`
+    pub mounts: Vec<String>,
+    /// Mounts requested with `--mount` or the project file, without the working directory,
+    /// scratch and auto mounts
+    #[serde(default)]
+    pub user_mounts: Vec<String>,
+    pub working_dir: Utf8PathBuf,
+    pub host: String,
+    pub started: String,
+    pub finished: Option<String>,
+    pub wall_time_secs: Option<f64>,
+    pub success: Option<bool>,
+    pub exit_code: Option<i32>,
+    /// Files referenced by the arguments with their blake3 hashes
+    pub inputs: Vec<InputFile>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    /// GPU selection passed to the engine, `None` when the run had no GPU request
+    #[serde(default)]
+    pub gpus: Option<String>,
+    /// `--cpus`, `--memory` and `--shm-size` limits of the run
+    #[serde(default)]
+    pub limits: Limits,
+    /// Host paths in the arguments rewritten to container paths
+    #[serde(default)]
+    pub rewrites: Vec<Rewrite>,
+    /// Whether `--no-auto-mount` was given
+    #[serde(default)]
+    pub no_auto_mount: bool,
+    /// Environment variables from the project file, `--env-file` and `--env`. Values of variables
+    /// that look like secrets are recorded as `blake3:<hash>`, see [`is_secret`]
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    #[serde(skip, default = "Instant::now")]
+    start: Instant,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputFile {
+    pub path: String,
+    pub blake3: String,
+}
+
+impl Manifest {
+    /// Start recording a run of `app` with `args` (command line and project file arguments),
+    /// hashes every existing file they reference.
+    pub fn new(
+        app: &dyn AppSpec,
+        engine: &str,
+        args: &[String],
+        options: &RunOptions,
+        working_dir: &Utf8Path,
+    ) -> Result<Self> {
+        // hash the host files behind rewritten arguments
+        let host_args: Vec<String> = args
+            .iter()
+            .map(|arg| {
+                let rewrite = options.rewrites.iter().find(|r| &r.to == arg);
+                rewrite.map_or(arg, |r| &r.from).clone()
+            })
+            .collect();
+
+        Ok(Manifest {
+            rc_version: env!("CARGO_PKG_VERSION").into(),
+            app: app.name().into(),
+            engine: engine.into(),
+            image: None,
+            digest: None,
+            pixi_recipe_hash: None,
+            native_prefix: None,
+            user_args: options.user_args.clone(),
+            args: Vec::new(),
+            mounts: Vec::new(),
+            user_mounts: options.mounts.iter().map(|m| m.to_string()).collect(),
+            working_dir: working_dir.to_path_buf(),
+            host: util::hostname(),
+            started: timestamp(SystemTime::now()),
+            finished: None,
+            wall_time_secs: None,
+            success: None,
+            exit_code: None,
+            inputs: input_files(&host_args, working_dir)?,
+            tags: options.tags.clone(),
+            note: options.note.clone(),
+            gpus: options.gpus.as_ref().map(|g| g.to_string()),
+            limits: options.limits.clone(),
+            rewrites: options.rewrites.clone(),
+            no_auto_mount: options.no_auto_mount,
+            env: options
+                .env
+                .iter()
+                .map(|(key, value)| match is_secret(key) {
+                    true => (key.clone(), secret_hash(value)),
+                    false => (key.clone(), value.clone()),
+                })
+                .collect(),
+            start: Instant::now(),
+        })
+    }
+
+    /// Record the container image, taking the digest from a pinned reference when present.
+    pub fn set_image(&mut self, image: &str) {
+        self.digest = ImageRef::parse(image).digest;
+        self.image = Some(image.into());
+    }
+
+    /// Restart the clock when the app process is launched, so image pulls and environment builds
+    /// before it do not count towards `started` and `wall_time_secs`.
+    pub fn launched(&mut self) {
+        self.started = timestamp(SystemTime::now());
+        self.start = Instant::now();
+    }
+
+    pub fn finish(&mut self, result: &CommandResults) {
+        self.finished = Some(timestamp(SystemTime::now()));
+        self.wall_time_secs = Some(self.start.elapsed().as_secs_f64());
+        self.success = Some(result.success);
+        self.exit_code = result.code;
+    }
+}
+
+/// Existing files referenced by `args`, either directly or as the value of `key=value`.
+fn input_files(args: &[String], working_dir: &Utf8Path) -> Result<Vec<InputFile>> {
+    let mut inputs: Vec<InputFile> = Vec::new();
+    for arg in args {
+        let candidates = std::iter::once(arg.as_str()).chain(arg.split_once('=').map(|(_, v)| v));
+        for candidate in candidates {
+            if candidate.is_empty() || inputs.iter().any(|i| i.path == candidate) {
+                continue;
+            }
+            let path = working_dir.join(candidate);
+            if path.is_file() {
+                inputs.push(InputFile {
+                    path: candidate.into(),
+                    blake3: hash_file(&path)?,
+                });
+            }
+        }
+    }
+    Ok(inputs)
+}
+
+/// Whether the environment variable `key` probably holds a credential, whose value is not written
+/// to the manifest.
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_uppercase();
+    [
+        "TOKEN",
+        "SECRET",
+        "PASSWORD",
+        "PASSWD",
+        "CREDENTIAL",
+        "API_KEY",
+        "ACCESS_KEY",
+        "PRIVATE_KEY",
+    ]
+    .iter()
+    .any(|s| key.contains(s))
+}
+
+/// How the value of a secret environment variable is recorded in the manifest.
+pub fn secret_hash(value: &str) -> String {
+    format!("blake3:{}", blake3::hash(value.as_bytes()).to_hex())
+}
+
+pub fn hash_file(path: &Utf8Path) -> Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(fs::File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+pub fn timestamp(t: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(t).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::TempDir;
+    use assert_fs::prelude::*;
+
+    #[test]
+    fn input_files_are_detected_and_hashed() {
+        let dir = TempDir::new().unwrap();
+        dir.child("1abc.pdb").write_str("ATOM").unwrap();
+        dir.child("inputs/motif.pdb").write_str("HETATM").unwrap();
+        let working_dir = Utf8Path::from_path(dir.path()).unwrap();
+
+        let args: Vec<String> = [
+            "-s",
+            "1abc.pdb",
+            "inference.input_pdb=inputs/motif.pdb",
+            "missing.pdb",
+            "1abc.pdb",
+        ]
+        .map(String::from)
+        .into();
+
+        let inputs = input_files(&args, working_dir).unwrap();
+        assert_eq!(
+            inputs.iter().map(|i| i.path.as_str()).collect::<Vec<_>>(),
+            ["1abc.pdb", "inputs/motif.pdb"]
+        );
+        assert_eq!(inputs[0].blake3, blake3::hash(b"ATOM").to_hex().to_string());
+    }
+
+    #[test]
+    fn secret_env_values_are_hashed() {
+        assert!(is_secret("HF_TOKEN"));
+        assert!(is_secret("wandb_api_key"));
+        assert!(!is_secret("OMP_NUM_THREADS"));
+        assert!(!is_secret("TORCH_HOME"));
+
+        let dir = TempDir::new().unwrap();
+        let working_dir = Utf8Path::from_path(dir.path()).unwrap();
+        let options = RunOptions {
+            env: BTreeMap::from([
+                ("HF_TOKEN".to_string(), "hf_abc".to_string()),
+                ("TORCH_HOME".to_string(), "/models".to_string()),
+            ]),
+            ..Default::default()
+        };
+        let app = crate::app::App::Rosetta.spec();
+        let manifest = Manifest::new(app, "docker", &[], &options, working_dir).unwrap();
+        assert_eq!(manifest.env["TORCH_HOME"], "/models");
+        assert_eq!(manifest.env["HF_TOKEN"], secret_hash("hf_abc"));
+        assert!(!manifest.env["HF_TOKEN"].contains("hf_abc"));
+    }
+
+    #[test]
+    fn wall_time_starts_at_launch() {
+        let dir = TempDir::new().unwrap();
+        let working_dir = Utf8Path::from_path(dir.path()).unwrap();
+        let app = crate::app::App::Rosetta.spec();
+        let mut manifest =
+            Manifest::new(app, "docker", &[], &RunOptions::default(), working_dir).unwrap();
+
+        // stands in for pulling the image
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        manifest.launched();
+        manifest.finish(&CommandResults {
+            stdout: String::new(),
+            stderr: String::new(),
+            success: true,
+            code: Some(0),
+        });
+        assert!(manifest.wall_time_secs.unwrap() < 0.3);
+    }
+
+    #[test]
+    fn concurrent_runs_reserve_distinct_ids() {
+        let dir = TempDir::new().unwrap();
+        let working_dir = Utf8Path::from_path(dir.path()).unwrap();
+
+        let mut ids: Vec<u32> = std::thread::scope(|s| {
+            let runs: Vec<_> = (0..16)
+                .map(|_| s.spawn(|| Telemetry::new(working_dir).unwrap().id()))
+                .collect();
+            runs.into_iter().map(|r| r.join().unwrap()).collect()
+        });
+        ids.sort();
+        assert_eq!(ids, (0..16).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn find_accepts_ids_last_and_paths() {
+        let dir = TempDir::new().unwrap();
+        let working_dir = Utf8Path::from_path(dir.path()).unwrap();
+
+        assert!(Telemetry::find(working_dir, "last").is_err());
+
+        for _ in 0..2 {
+            Telemetry::new(working_dir).unwrap();
+        }
+
+        assert_eq!(Telemetry::find(working_dir, "0").unwrap().id(), 0);
+        assert_eq!(Telemetry::find(working_dir, "0001").unwrap().id(), 1);
+        assert_eq!(Telemetry::find(working_dir, "last").unwrap().id(), 1);
+        assert!(Telemetry::find(working_dir, "7").is_err());
+        assert!(Telemetry::find(working_dir, "latest").is_err());
+
+        let log = working_dir.join(".0001.rc.log");
+        assert!(log.exists());
+        let t = Telemetry::find(Utf8Path::new("/nonexistent"), log.as_str()).unwrap();
+        assert_eq!(t.id(), 1);
+        assert_eq!(t.log_file_name(), log);
     }
 }
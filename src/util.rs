@@ -6,8 +6,8 @@ pub mod yansi;
 use std::io::{self, Write};
 use std::time::Duration;
 
-pub use command::Command;
-pub use dir_guard::ensure_dir_signature;
+pub use command::{Command, CommandResults};
+pub use dir_guard::{dir_signature, dir_signature_is_current, ensure_dir_signature};
 
 #[allow(dead_code)]
 /// Fancy sleep function with a countdown message.
@@ -28,6 +28,45 @@ pub fn sleep(message: impl AsRef<str>, seconds: usize) {
     io::stdout().flush().unwrap();
 }
 
+/// Name of this host, empty when it cannot be determined.
+pub fn hostname() -> String {
+    #[cfg(unix)]
+    if let Ok(name) = nix::unistd::gethostname() {
+        return name.to_string_lossy().into();
+    }
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_default()
+}
+
+/// Make `path`, and everything below it when it is a directory, readable by the owning group:
+/// adds `g+r`, plus `g+x` for directories and executables.
+pub fn share_with_group(path: &camino::Utf8Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let metadata = std::fs::symlink_metadata(path)?;
+        if metadata.is_symlink() {
+            return Ok(());
+        }
+        let mode = metadata.permissions().mode();
+        let exec = if metadata.is_dir() || mode & 0o100 != 0 {
+            0o010
+        } else {
+            0
+        };
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode | 0o040 | exec))?;
+
+        if metadata.is_dir() {
+            for entry in path.read_dir_utf8()? {
+                share_with_group(entry?.path())?;
+            }
+        }
+    }
+    Ok(())
+}
+
 macro_rules! include_asset {
     ($path:literal) => {
         include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/", $path))
@@ -9,6 +9,8 @@ use std::{
 
 use yansi::{Condition, Paint};
 
+use crate::telemetry::is_secret;
+
 #[derive(Debug, Clone)]
 enum ExecutionMode {
     Silent,
@@ -21,6 +23,7 @@ pub struct Command {
     args: Vec<String>,
     message: Option<String>,
     cd: Option<PathBuf>,
+    env: Vec<(String, String)>,
     execution_mode: ExecutionMode,
 }
 
@@ -29,6 +32,8 @@ pub struct CommandResults {
     pub stdout: String,
     pub stderr: String,
     pub success: bool,
+    /// Exit code of the process, `None` when it was terminated by a signal
+    pub code: Option<i32>,
 }
 
 #[allow(dead_code)]
@@ -38,6 +43,7 @@ impl Command {
             command: command.into(),
             args: Vec::new(),
             cd: None,
+            env: Vec::new(),
             message: None,
             execution_mode: ExecutionMode::Silent,
         }
@@ -77,6 +83,23 @@ impl Command {
         self
     }
 
+    /// Set an environment variable for the child process
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env
+            .extend(vars.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
     pub fn silent(mut self) -> Self {
         self.execution_mode = ExecutionMode::Silent;
         self
@@ -100,6 +123,7 @@ impl Command {
         if let Some(dir) = &self.cd {
             cmd.current_dir(dir);
         }
+        cmd.envs(self.env.iter().map(|(k, v)| (k, v)));
         cmd
     }
 
@@ -134,9 +158,12 @@ impl Command {
             cmd.stdout(Stdio::piped());
             cmd.stderr(Stdio::piped());
 
-            let mut child = cmd
-                .spawn()
-                .unwrap_or_else(|_| panic!("Command: {} failed to start", self.command.red()));
+            let mut child = cmd.spawn().unwrap_or_else(|_| {
+                panic!(
+                    "Command: {} failed to start, run `rc doctor` to check your setup",
+                    self.command.red()
+                )
+            });
 
             let child_stdout = child.stdout.take().expect("Failed to capture stdout");
             let child_stderr = child.stderr.take().expect("Failed to capture stderr");
@@ -154,6 +181,7 @@ impl Command {
                     stdout: String::from_utf8_lossy(&stdout_bytes).into(),
                     stderr: String::from_utf8_lossy(&stderr_bytes).into(),
                     success: status.success(),
+                    code: status.code(),
                 }
             })
         } else {
@@ -162,6 +190,7 @@ impl Command {
                 stdout: String::from_utf8_lossy(&o.stdout).into(),
                 stderr: String::from_utf8_lossy(&o.stderr).into(),
                 success: o.status.success(),
+                code: o.status.code(),
             }
         }
     }
@@ -190,7 +219,12 @@ impl Command {
         }
 
         cmd.spawn()
-            .unwrap_or_else(|_| panic!("command: {} failed to start", self.command.red()))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "command: {} failed to start, run `rc doctor` to check your setup",
+                    self.command.red()
+                )
+            })
             .wait()
             .expect("failed to wait on child")
             .success()
@@ -208,7 +242,7 @@ impl fmt::Display for Command {
 
         write!(
             f,
-            "{}{}{} {}",
+            "{}{}{}{} {}",
             self.message
                 .as_deref()
                 .map_or("".into(), |m| format!("{m}\n"))
@@ -220,6 +254,15 @@ impl fmt::Display for Command {
                 .unwrap_or_default()
                 .dim()
                 .whenever(use_color),
+            self.env
+                .iter()
+                .map(|(k, v)| match is_secret(k) {
+                    true => format!("{k}=... "),
+                    false => format!("{k}={} ", shell_escape::escape(v.into())),
+                })
+                .collect::<String>()
+                .dim()
+                .whenever(use_color),
             self.command.bright_white().whenever(use_color),
             self.args
                 .iter()
@@ -315,6 +358,14 @@ mod tests {
         assert!(alt_output.contains("hello world"));
     }
 
+    #[test]
+    fn test_display_hides_secrets() {
+        let cmd = Command::new("sbatch")
+            .env("HF_TOKEN", "hf_abc")
+            .env("OMP_NUM_THREADS", "2");
+        assert_eq!(format!("{cmd}"), "HF_TOKEN=... OMP_NUM_THREADS=2 sbatch ");
+    }
+
     #[test]
     fn test_call_live() {
         let result = Command::shell("echo stdout_test && echo stderr_test >&2")
@@ -366,6 +417,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_env() {
+        let cmd = Command::shell("echo $RC_TEST_VAR").env("RC_TEST_VAR", "some value");
+        assert_eq!(cmd.try_call().stdout.trim(), "some value");
+        assert!(format!("{cmd}").starts_with("RC_TEST_VAR='some value' sh"));
+    }
+
     #[test]
     fn test_add_arg_basic() {
         let mut cmd = Command::new("echo");
@@ -47,6 +47,19 @@ where
     Ok(state)
 }
 
+/// Whether `dir` exists and was built from exactly `signature_parts`.
+pub fn dir_signature_is_current<S: AsRef<[u8]>>(
+    dir: &Utf8Path,
+    signature_parts: &[S],
+) -> Result<bool> {
+    signature_matches(&dir.join(DIR_SIGNATURE_FILE), &hash_slices(signature_parts))
+}
+
+/// Signature `dir` was last built with, `None` when it was never built.
+pub fn dir_signature(dir: &Utf8Path) -> Option<String> {
+    std::fs::read_to_string(dir.join(DIR_SIGNATURE_FILE)).ok()
+}
+
 fn hash_slices<S: AsRef<[u8]>>(data: &[S]) -> String {
     let mut hasher = blake3::Hasher::new();
     for s in data {
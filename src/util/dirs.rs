@@ -1,7 +1,40 @@
 use camino::Utf8PathBuf;
 use home::home_dir;
 
+/// Root directory for cached images and native environments, honoring `cache.root` from the
+/// user configuration (and its `RC_CACHE_DIR` environment override).
 pub fn cache_root() -> Utf8PathBuf {
-    let path = home_dir().unwrap().join(".cache/rosettacommons/rc");
-    Utf8PathBuf::from_path_buf(path).expect("path is not valid UTF-8")
+    crate::config::settings().cache_root()
+}
+
+/// Site-wide, usually read-only, root of images and native environments installed by admins with
+/// `rc install --shared`, from `shared.root` (or its `RC_SHARED_ROOT` environment override).
+pub fn shared_root() -> Option<Utf8PathBuf> {
+    crate::config::settings().shared_root()
+}
+
+/// Built-in cache location used when `cache.root` is not configured:
+/// `$XDG_CACHE_HOME/rosettacommons/rc` or `~/.cache/rosettacommons/rc`.
+pub fn default_cache_root() -> Utf8PathBuf {
+    let base = std::env::var("XDG_CACHE_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(Utf8PathBuf::from)
+        .unwrap_or_else(|| home().join(".cache"));
+    base.join("rosettacommons/rc")
+}
+
+/// Directory holding the user configuration file: `$XDG_CONFIG_HOME/rosettacommons/rc` or
+/// `~/.config/rosettacommons/rc`.
+pub fn config_root() -> Utf8PathBuf {
+    let base = std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(Utf8PathBuf::from)
+        .unwrap_or_else(|| home().join(".config"));
+    base.join("rosettacommons/rc")
+}
+
+pub fn home() -> Utf8PathBuf {
+    Utf8PathBuf::from_path_buf(home_dir().unwrap()).expect("path is not valid UTF-8")
 }
@@ -0,0 +1,314 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const IMAGE: &str = "hpc/rosettacommons-rosetta:serial.sif";
+
+/// `docker` shim whose `save -o <file>` writes a stand-in archive.
+const DOCKER_SHIM: &str = r#"#!/usr/bin/env bash
+echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+if [ "$1" = "save" ]; then printf 'layers of %s' "$4" > "$3"; fi
+exit 0
+"#;
+
+/// A host with its own cache and invocation log, the login node or the compute node.
+fn host() -> common::Sandbox {
+    let host = common::Sandbox::new();
+    host.shim.install_script("docker", DOCKER_SHIM);
+    host
+}
+
+fn rc(host: &common::Sandbox, args: &[&str]) -> assert_cmd::Command {
+    let mut cmd = host.rc();
+    cmd.args(args);
+    cmd
+}
+
+#[test]
+fn sif_images_move_to_an_offline_host() {
+    let login = host();
+    let bundle = login.path("bundle.tar");
+    rc(
+        &login,
+        &[
+            "bundle",
+            "export",
+            "rosetta",
+            "-e",
+            "singularity",
+            "-o",
+            &bundle,
+        ],
+    )
+    .assert()
+    .success();
+    assert!(login.log().contains("singularity pull"), "{}", login.log());
+
+    let compute = host();
+    rc(&compute, &["bundle", "import", &bundle])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(IMAGE));
+
+    let image = compute.dir.child("cache").child(IMAGE);
+    assert_eq!(
+        std::fs::read(image.path()).unwrap(),
+        std::fs::read(login.dir.child("cache").child(IMAGE).path()).unwrap()
+    );
+    assert_eq!(compute.log(), "", "nothing is pulled on the compute node");
+    // the staging directory is removed again
+    let cache = std::fs::read_dir(compute.dir.child("cache").path()).unwrap();
+    assert_eq!(cache.count(), 1);
+}
+
+#[test]
+fn docker_images_are_saved_and_loaded() {
+    let login = host();
+    let bundle = login.path("bundle.tar");
+    rc(
+        &login,
+        &[
+            "bundle", "export", "rosetta", "score", "-e", "docker", "-o", &bundle,
+        ],
+    )
+    .assert()
+    .success();
+    assert!(
+        login.log().contains("docker save -o ") && login.log().contains("rosettacommons/rosetta"),
+        "{}",
+        login.log()
+    );
+
+    let compute = host();
+    rc(&compute, &["bundle", "import", &bundle])
+        .assert()
+        .success();
+    assert_eq!(
+        compute.log().matches("docker load -i ").count(),
+        2,
+        "{}",
+        compute.log()
+    );
+}
+
+#[test]
+fn damaged_bundles_are_rejected() {
+    let login = host();
+    let bundle = login.path("bundle.tar");
+    rc(
+        &login,
+        &[
+            "bundle",
+            "export",
+            "rosetta",
+            "-e",
+            "singularity",
+            "-o",
+            &bundle,
+        ],
+    )
+    .assert()
+    .success();
+
+    // swap the image inside the bundle for another one with the same name
+    let unpacked = login.dir.child("unpacked");
+    unpacked.create_dir_all().unwrap();
+    let tar = |args: &[&str]| {
+        let status = std::process::Command::new("tar")
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    tar(&["-x", "-f", &bundle, "-C", &login.path("unpacked")]);
+    unpacked
+        .child("images/rosettacommons-rosetta:serial.sif")
+        .write_str("tampered")
+        .unwrap();
+    tar(&["-c", "-f", &bundle, "-C", &login.path("unpacked"), "."]);
+
+    let compute = host();
+    rc(&compute, &["bundle", "import", &bundle])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("checksum mismatch"));
+    compute
+        .dir
+        .child("cache")
+        .child(IMAGE)
+        .assert(predicate::path::missing());
+}
+
+#[test]
+fn auto_is_not_an_export_engine() {
+    let login = host();
+    rc(
+        &login,
+        &[
+            "bundle",
+            "export",
+            "rosetta",
+            "-e",
+            "auto",
+            "-o",
+            &login.path("b.tar"),
+        ],
+    )
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("not `auto`"));
+}
+
+#[test]
+fn apps_without_a_native_environment_are_not_exported_for_none() {
+    let login = host();
+    rc(
+        &login,
+        &[
+            "bundle",
+            "export",
+            "colabfold",
+            "-e",
+            "none",
+            "-o",
+            &login.path("b.tar"),
+        ],
+    )
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("has no native environment"));
+}
+
+#[test]
+fn environments_are_exported_from_the_cache_not_the_shared_root() {
+    let login = host();
+    login
+        .shim
+        .install_script("pixi", "#!/usr/bin/env bash\nexit 0\n");
+    let shared = login.path("shared");
+    login
+        .rc()
+        .env("RC_SHARED_ROOT", &shared)
+        .args(["install", "rosetta", "-e", "none", "--shared"])
+        .assert()
+        .success();
+
+    let bundle = login.path("bundle.tar");
+    login
+        .rc()
+        .env("RC_SHARED_ROOT", &shared)
+        .args(["bundle", "export", "rosetta", "-e", "none", "-o", &bundle])
+        .assert()
+        .success();
+
+    let manifest = std::process::Command::new("tar")
+        .args(["-x", "-O", "-f", &bundle, "rc-bundle.json"])
+        .output()
+        .unwrap();
+    let manifest: serde_json::Value = serde_json::from_slice(&manifest.stdout).unwrap();
+    assert_eq!(
+        manifest["entries"][0]["native_root"],
+        login.path("cache/native/Rosetta")
+    );
+}
+
+#[test]
+fn sif_images_pinned_by_the_lock_keep_their_run_name() {
+    const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    let pinned = format!("hpc/rosettacommons-rosetta@{DIGEST}.sif");
+
+    let login = host();
+    login
+        .dir
+        .child("work/rc.lock")
+        .write_str(&format!(
+            "[apps.rosetta]\nimage = \"rosettacommons/rosetta:serial\"\ndigest = \"{DIGEST}\"\n"
+        ))
+        .unwrap();
+    let bundle = login.path("bundle.tar");
+    rc(
+        &login,
+        &[
+            "bundle",
+            "export",
+            "rosetta",
+            "-e",
+            "singularity",
+            "-w",
+            &login.path("work"),
+            "-o",
+            &bundle,
+        ],
+    )
+    .assert()
+    .success();
+    assert!(
+        login
+            .log()
+            .contains(&format!("docker://rosettacommons/rosetta@{DIGEST}")),
+        "{}",
+        login.log()
+    );
+
+    let compute = host();
+    rc(&compute, &["bundle", "import", &bundle])
+        .assert()
+        .success();
+    compute
+        .dir
+        .child("cache")
+        .child(&pinned)
+        .assert(predicate::path::exists());
+    compute
+        .dir
+        .child("cache")
+        .child(IMAGE)
+        .assert(predicate::path::missing());
+}
+
+#[test]
+fn environments_are_not_imported_into_another_root() {
+    let login = host();
+    let content = login.dir.child("content");
+    content
+        .child("native/Rosetta.tar")
+        .write_str("not a tar")
+        .unwrap();
+    content
+        .child("rc-bundle.json")
+        .write_str(
+            r#"{
+  "rc_version": "0",
+  "engine": "none",
+  "created": "",
+  "entries": [
+    {
+      "app": "Rosetta",
+      "image": null,
+      "file": "native/Rosetta.tar",
+      "blake3": "",
+      "native_root": "/elsewhere/native/Rosetta"
+    }
+  ]
+}"#,
+        )
+        .unwrap();
+    let bundle = login.path("bundle.tar");
+    let status = std::process::Command::new("tar")
+        .args(["-c", "-f", &bundle, "-C", &login.path("content"), "."])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let compute = host();
+    let existing = compute.dir.child("cache/native/Rosetta/.pixi");
+    existing.create_dir_all().unwrap();
+    rc(&compute, &["bundle", "import", &bundle])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Pixi environments do not work from another path",
+        ));
+    existing.assert(predicate::path::exists());
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+/// Sandbox whose cache holds one current `.sif`, one stale `.sif` and one native environment.
+fn populated_cache() -> common::Sandbox {
+    let sandbox = common::Sandbox::bare();
+    let cache = sandbox.dir.child("cache");
+    cache
+        .child("hpc/rosettacommons-rosetta:serial.sif")
+        .write_binary(&[0u8; 2048])
+        .unwrap();
+    cache
+        .child("hpc/rosettacommons-retired-app.sif")
+        .write_binary(&[0u8; 1024])
+        .unwrap();
+    cache
+        .child("native/Rosetta/pixi.toml")
+        .write_str("[workspace]\n")
+        .unwrap();
+    sandbox
+}
+
+#[test]
+fn cache_ls_reports_owner_and_staleness() {
+    let sandbox = populated_cache();
+
+    let output = sandbox
+        .rc()
+        .args(["cache", "ls", "--json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let artifacts: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let artifacts = artifacts.as_array().unwrap();
+    assert_eq!(artifacts.len(), 3);
+
+    let find = |suffix: &str| {
+        artifacts
+            .iter()
+            .find(|a| a["path"].as_str().unwrap().ends_with(suffix))
+            .unwrap_or_else(|| panic!("{suffix} not listed"))
+    };
+
+    let current = find("rosettacommons-rosetta:serial.sif");
+    assert_eq!(current["kind"], "hpc");
+    assert_eq!(current["stale"], false);
+    assert_eq!(current["size"], 2048);
+
+    let retired = find("rosettacommons-retired-app.sif");
+    assert_eq!(retired["stale"], true);
+    assert!(retired["app"].is_null());
+
+    let native = find("native/Rosetta");
+    assert_eq!(native["kind"], "native");
+    assert_eq!(native["app"], "rosetta");
+}
+
+#[test]
+fn cache_du_totals() {
+    let sandbox = populated_cache();
+
+    sandbox
+        .rc()
+        .args(["cache", "du", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"stale\": 1024"))
+        .stdout(predicate::str::contains("\"hpc\": 3072"));
+}
+
+#[test]
+fn cache_prune_stale() {
+    let sandbox = populated_cache();
+    let cache = sandbox.dir.child("cache");
+    let retired = cache.child("hpc/rosettacommons-retired-app.sif");
+
+    sandbox
+        .rc()
+        .args(["cache", "prune", "--stale", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Would remove"));
+    retired.assert(predicate::path::exists());
+
+    sandbox
+        .rc()
+        .args(["cache", "prune", "--stale"])
+        .assert()
+        .success();
+    retired.assert(predicate::path::missing());
+    cache
+        .child("hpc/rosettacommons-rosetta:serial.sif")
+        .assert(predicate::path::exists());
+
+    sandbox
+        .rc()
+        .args(["cache", "prune", "--older-than", "30d"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Nothing to prune"));
+
+    sandbox
+        .rc()
+        .args(["cache", "prune", "--app", "rosetta"])
+        .assert()
+        .success();
+    cache
+        .child("native/Rosetta")
+        .assert(predicate::path::missing());
+}
+
+#[test]
+fn cache_prune_requires_a_filter() {
+    let sandbox = populated_cache();
+    sandbox.rc().args(["cache", "prune"]).assert().failure();
+}
@@ -0,0 +1,45 @@
+mod common;
+
+fn json(cmd: &mut assert_cmd::Command) -> serde_json::Value {
+    let output = cmd.output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn apps_lists_every_app_with_install_status() {
+    let sandbox = common::Sandbox::new();
+
+    let apps = json(sandbox.rc().args(["apps", "--json"]));
+    let apps = apps.as_array().unwrap();
+    assert_eq!(apps.len(), 11);
+
+    let colabfold = apps.iter().find(|a| a["name"] == "colabfold").unwrap();
+    assert_eq!(colabfold["native"], false);
+    let status = |engine: &str| {
+        colabfold["engines"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["engine"] == engine)
+            .unwrap()["status"]
+            .clone()
+    };
+    assert_eq!(status("none"), "unsupported");
+    assert_eq!(status("apptainer"), "not-installed");
+}
+
+#[test]
+fn info_describes_app() {
+    let sandbox = common::Sandbox::bare();
+
+    let info = json(sandbox.rc().args(["info", "foundry", "--json"]));
+    assert_eq!(info["name"], "foundry");
+    assert_eq!(
+        info["homepage"],
+        "https://github.com/RosettaCommons/foundry"
+    );
+    assert_eq!(info["image"], "rosettacommons/foundry:weights");
+    assert_eq!(info["required_args"][0], "<mpnn|rf3|rfd3>");
+    assert_eq!(info["native"], true);
+}
@@ -1,5 +1,6 @@
 mod macros;
 mod path_shim;
+mod sandbox;
 
 #[allow(unused)]
 pub(crate) use macros::engine_tests;
@@ -7,6 +8,9 @@ pub(crate) use macros::engine_tests;
 #[allow(unused)]
 pub use path_shim::ContainerPathShim;
 
+#[allow(unused)]
+pub use sandbox::Sandbox;
+
 #[allow(dead_code)]
 #[cfg_attr(not(feature = "docker-tests"), ignore)]
 pub fn docker_clear_cache() {
@@ -7,6 +7,10 @@ use std::path::{Path, PathBuf};
 
 const SHIM_SCRIPT: &str = r#"#!/usr/bin/env bash
 echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+# `singularity/apptainer pull <image.sif> <source>` produces a minimal SIF header
+case "$(basename "$0") $1" in
+  "singularity pull" | "apptainer pull") printf '#!/usr/bin/env run-singularity\n\0SIF_MAGIC\0' > "$2" ;;
+esac
 exit 0
 "#;
 
@@ -17,7 +21,7 @@ pub struct ContainerPathShim {
 }
 
 impl ContainerPathShim {
-    pub const BIN_SHIMS: [&str; 3] = ["docker", "singularity", "apptainer"];
+    pub const BIN_SHIMS: [&str; 4] = ["docker", "podman", "singularity", "apptainer"];
 
     pub fn new() -> Self {
         let root = std::path::PathBuf::from("target/fixtures");
@@ -40,7 +44,7 @@ impl ContainerPathShim {
         self.bin.clone()
     }
 
-    /// Write all three shims (`docker`, `singularity`, `apptainer`) into the bin dir.
+    /// Write all shims (`docker`, `podman`, `singularity`, `apptainer`) into the bin dir.
     pub fn install_all(&self) -> PathBuf {
         for name in Self::BIN_SHIMS {
             self.install(name);
@@ -55,6 +59,13 @@ impl ContainerPathShim {
         self.bin.clone()
     }
 
+    /// Write a custom `<name>` script into the bin dir, for tools whose output matters to the test.
+    #[allow(dead_code)]
+    pub fn install_script(&self, name: &str, script: &str) -> PathBuf {
+        write_executable(&self.bin_dir().join(name), script);
+        self.bin.clone()
+    }
+
     /// Returns a shell snippet that constrains PATH to only this directory.
     ///
     /// Example use in a test:
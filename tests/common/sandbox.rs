@@ -0,0 +1,67 @@
+use assert_fs::TempDir;
+use assert_fs::prelude::*;
+
+use super::ContainerPathShim;
+
+/// A host of its own for running `rc`: the configuration file, cache and invocation log live in a
+/// temporary directory and the container shims come first on `PATH`. `RC_*` variables of the
+/// calling environment are cleared, so settings of the machine running the tests do not leak in.
+pub struct Sandbox {
+    pub dir: TempDir,
+    pub shim: ContainerPathShim,
+}
+
+#[allow(dead_code)]
+impl Sandbox {
+    /// Sandbox with the `docker`, `podman`, `singularity` and `apptainer` shims installed.
+    pub fn new() -> Self {
+        let sandbox = Self::bare();
+        sandbox.shim.install_all();
+        sandbox
+    }
+
+    /// Sandbox without any shims, for tests that install only the tools they need.
+    pub fn bare() -> Self {
+        Sandbox {
+            dir: TempDir::new().expect("create temp dir"),
+            shim: ContainerPathShim::new(),
+        }
+    }
+
+    /// Absolute path of `name` inside the sandbox.
+    pub fn path(&self, name: &str) -> String {
+        self.dir.child(name).path().to_str().unwrap().into()
+    }
+
+    /// Working directory `work` inside the sandbox, created on first use.
+    pub fn work_dir(&self) -> String {
+        self.dir.child("work").create_dir_all().unwrap();
+        self.path("work")
+    }
+
+    /// `rc` running in the sandbox, for tests that need to spawn it themselves.
+    pub fn command(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new(assert_cmd::cargo::cargo_bin!());
+        for (key, _) in std::env::vars().filter(|(key, _)| key.starts_with("RC_")) {
+            cmd.env_remove(key);
+        }
+        cmd.env("RC_CONFIG", self.dir.child("config.toml").path())
+            .env("RC_CACHE_DIR", self.dir.child("cache").path())
+            .env(
+                "TEST_INVOCATIONS_LOG",
+                self.dir.child("invocations.log").path(),
+            )
+            .envs(self.shim.env_overrides());
+        cmd
+    }
+
+    /// `rc` running in the sandbox.
+    pub fn rc(&self) -> assert_cmd::Command {
+        assert_cmd::Command::from_std(self.command())
+    }
+
+    /// Invocations of the shims so far.
+    pub fn log(&self) -> String {
+        std::fs::read_to_string(self.dir.child("invocations.log").path()).unwrap_or_default()
+    }
+}
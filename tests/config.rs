@@ -0,0 +1,274 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+#[test]
+fn config_path_honors_rc_config() {
+    let sandbox = common::Sandbox::bare();
+    let config = sandbox.dir.child("config.toml");
+
+    sandbox
+        .rc()
+        .args(["config", "path"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(config.path().to_str().unwrap()));
+}
+
+#[test]
+fn config_set_get_unset() {
+    let sandbox = common::Sandbox::bare();
+    let config = sandbox.dir.child("config.toml");
+
+    sandbox
+        .rc()
+        .args(["config", "get", "engine.default"])
+        .assert()
+        .success()
+        .stdout("auto\n");
+
+    sandbox
+        .rc()
+        .args(["config", "set", "engine.default", "apptainer"])
+        .assert()
+        .success();
+
+    config.assert(predicate::str::contains("[engine]"));
+    config.assert(predicate::str::contains("default = \"apptainer\""));
+
+    sandbox
+        .rc()
+        .args(["config", "get", "engine.default", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"origin\":\"file\""))
+        .stdout(predicate::str::contains("\"value\":\"apptainer\""));
+
+    sandbox
+        .rc()
+        .args(["config", "unset", "engine.default"])
+        .assert()
+        .success();
+
+    sandbox
+        .rc()
+        .args(["config", "get", "engine.default"])
+        .assert()
+        .success()
+        .stdout("auto\n");
+}
+
+#[test]
+fn config_set_rejects_invalid_values() {
+    let sandbox = common::Sandbox::bare();
+    let config = sandbox.dir.child("config.toml");
+
+    sandbox
+        .rc()
+        .args(["config", "set", "engine.default", "vmware"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid engine"));
+
+    sandbox
+        .rc()
+        .args(["config", "set", "no.such.key", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown configuration key"));
+
+    config.assert(predicate::path::missing());
+}
+
+#[test]
+fn config_env_overrides_file() {
+    let sandbox = common::Sandbox::bare();
+    let config = sandbox.dir.child("config.toml");
+    config
+        .write_str("[apps.rosetta]\nimage = \"rosettacommons/rosetta:mpi\"\n")
+        .unwrap();
+
+    sandbox
+        .rc()
+        .args(["config", "show", "--json", "--origin"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"apps.rosetta.image\": \"rosettacommons/rosetta:mpi\"",
+        ))
+        .stdout(predicate::str::contains("\"apps.rosetta.image\": \"file\""));
+
+    sandbox
+        .rc()
+        .env("RC_ROSETTA_IMAGE", "rosettacommons/rosetta:latest")
+        .args(["config", "get", "apps.rosetta.image", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("rosettacommons/rosetta:latest"))
+        .stdout(predicate::str::contains("env RC_ROSETTA_IMAGE"));
+}
+
+#[test]
+fn configured_engine_is_used_for_run() {
+    let sandbox = common::Sandbox::new();
+    sandbox
+        .dir
+        .child("config.toml")
+        .write_str("[engine]\ndefault = \"singularity\"\n")
+        .unwrap();
+
+    sandbox
+        .rc()
+        .args(["run", "-w", &sandbox.work_dir(), "rosetta", "score"])
+        .assert()
+        .success();
+
+    let log_contents = sandbox.log();
+    assert!(
+        log_contents.contains("singularity run"),
+        "expected singularity to be invoked, got:\n{log_contents}"
+    );
+}
+
+#[test]
+fn project_file_is_applied_to_run() {
+    let sandbox = common::Sandbox::new();
+    let config = sandbox.dir.child("config.toml");
+    config
+        .write_str("[engine]\ndefault = \"singularity\"\n")
+        .unwrap();
+
+    let project = sandbox.dir.child("project");
+    project
+        .child(".rc.toml")
+        .write_str(
+            r#"
+            engine = "docker"
+            mounts = ["refs:/refs:ro"]
+            env = { HYDRA_FULL_ERROR = "1" }
+
+            [apps.rosetta]
+            args = ["-ignore_unrecognized_res", "-nstruct", "5"]
+            "#,
+        )
+        .unwrap();
+    let work_dir = project.child("campaign-1");
+    work_dir.create_dir_all().unwrap();
+    let run = || {
+        let mut cmd = sandbox.rc();
+        cmd.args([
+            "run",
+            "-w",
+            work_dir.path().to_str().unwrap(),
+            "rosetta",
+            "score",
+            "-nstruct",
+            "2",
+        ]);
+        cmd
+    };
+
+    run().assert().failure().stderr(
+        predicate::str::contains("mount source").and(predicate::str::contains("does not exist")),
+    );
+    assert_eq!(sandbox.log(), "");
+
+    project.child("refs").create_dir_all().unwrap();
+    run()
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Using project file"));
+
+    let log_contents = sandbox.log();
+    for part in [
+        format!(
+            "--volume {}:/refs:ro",
+            project.child("refs").path().display()
+        ),
+        "--env HYDRA_FULL_ERROR=1".into(),
+        "score -nstruct 2 -ignore_unrecognized_res\n".into(),
+    ] {
+        assert!(
+            log_contents.contains(&part),
+            "Expected log to contain:\n  {part}\n\nActual log contents:\n{log_contents}"
+        );
+    }
+    // the command line value is the only one
+    assert!(!log_contents.contains("-nstruct 5"), "{log_contents}");
+
+    sandbox
+        .rc()
+        .args([
+            "config",
+            "show",
+            "--json",
+            "-w",
+            work_dir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".rc.toml"))
+        .stdout(predicate::str::contains("-ignore_unrecognized_res"));
+}
+
+#[test]
+fn auto_engine_follows_priority() {
+    let sandbox = common::Sandbox::new();
+
+    sandbox
+        .rc()
+        .env("RC_ENGINE_PRIORITY", "apptainer,docker")
+        .args([
+            "run",
+            "-e",
+            "auto",
+            "-w",
+            &sandbox.work_dir(),
+            "rosetta",
+            "score",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Automatically selected container engine: apptainer",
+        ));
+
+    let log_contents = sandbox.log();
+    assert!(
+        log_contents.contains("apptainer run"),
+        "expected apptainer to be invoked, got:\n{log_contents}"
+    );
+}
+
+#[test]
+fn auto_engine_reports_missing_engines() {
+    let sandbox = common::Sandbox::bare();
+    let bin = sandbox.shim.install("docker");
+
+    sandbox
+        .rc()
+        .env("PATH", &bin)
+        .env("RC_ENGINE_PRIORITY", "singularity,none")
+        .args(["install", "colabfold"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Could not find a usable container engine for Colabfold (tried: singularity, none)",
+        ));
+}
+
+#[test]
+fn config_show_does_not_create_the_cache() {
+    let sandbox = common::Sandbox::bare();
+
+    sandbox
+        .rc()
+        .args(["config", "show", "--json"])
+        .assert()
+        .success();
+    sandbox
+        .dir
+        .child("cache")
+        .assert(predicate::path::missing());
+}
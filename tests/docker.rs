@@ -55,3 +55,212 @@ fn mock_docker_rosetta_score() {
         );
     }
 }
+
+#[test]
+fn run_manifest_is_written_next_to_log() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+    std::fs::write(sandbox.dir.path().join("work/1abc.pdb"), "ATOM").unwrap();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "docker", "-w", &work])
+        .args(["rosetta", "score", "-in:file:s", "1abc.pdb"])
+        .assert()
+        .success();
+
+    let manifest = std::fs::read_to_string(sandbox.dir.path().join("work/.0000.rc.json"))
+        .expect("run manifest should be written");
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+
+    assert_eq!(manifest["app"], "Rosetta");
+    assert_eq!(manifest["engine"], "docker");
+    assert_eq!(manifest["rc_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(manifest["success"], true);
+    assert_eq!(manifest["exit_code"], 0);
+    assert_eq!(
+        manifest["user_args"],
+        serde_json::json!(["score", "-in:file:s", "1abc.pdb"])
+    );
+    assert_eq!(manifest["inputs"][0]["path"], "1abc.pdb");
+    assert_eq!(
+        manifest["inputs"][0]["blake3"],
+        blake3::hash(b"ATOM").to_hex().to_string()
+    );
+    assert!(manifest["mounts"][0].as_str().unwrap().ends_with(":/w"));
+}
+
+/// Manifest of run `id` (e.g. `0000`) in the sandbox's working directory.
+fn manifest(sandbox: &common::Sandbox, id: &str) -> serde_json::Value {
+    let path = sandbox.dir.path().join(format!("work/.{id}.rc.json"));
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn rerun_replays_recorded_run() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "docker", "-w", &work])
+        .args(["rosetta", "score", "-out:level", "300"])
+        .assert()
+        .success();
+
+    // pretend the run used an image that is no longer current
+    let mut recorded = manifest(&sandbox, "0000");
+    recorded["digest"] = "sha256:recorded".into();
+    std::fs::write(
+        sandbox.dir.path().join("work/.0000.rc.json"),
+        recorded.to_string(),
+    )
+    .unwrap();
+
+    sandbox
+        .rc()
+        .args(["rerun", "0", "-w", &work])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("sha256:recorded"));
+
+    sandbox
+        .rc()
+        .args(["rerun", "last", "-w", &work, "-e", "singularity"])
+        .assert()
+        .success();
+
+    let rerun = manifest(&sandbox, "0002");
+    assert_eq!(rerun["engine"], "singularity");
+    assert_eq!(
+        rerun["user_args"],
+        serde_json::json!(["score", "-out:level", "300"])
+    );
+
+    let log_contents = sandbox.log();
+    assert_eq!(log_contents.matches("docker run").count(), 2);
+    assert!(log_contents.contains("singularity run"));
+}
+
+#[test]
+fn rerun_replays_run_options() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+    let library = sandbox.path("library");
+    std::fs::create_dir_all(&library).unwrap();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "docker", "-w", &work])
+        .args(["--mount", &format!("{library}:/library:ro")])
+        .args(["--cpus", "2", "--memory", "4g", "--gpus", "1"])
+        .args(["--image", "example.org/rosetta:custom", "--tag", "baseline"])
+        .args(["rosetta", "score"])
+        .assert()
+        .success();
+    sandbox
+        .rc()
+        .args(["rerun", "0", "-w", &work])
+        .assert()
+        .success();
+
+    let (run, rerun) = (manifest(&sandbox, "0000"), manifest(&sandbox, "0001"));
+    for key in ["user_mounts", "limits", "gpus", "image", "tags", "mounts"] {
+        assert_eq!(run[key], rerun[key], "{key}");
+    }
+    assert_eq!(
+        rerun["user_mounts"],
+        serde_json::json!([format!("{library}:/library:ro")])
+    );
+
+    let log = sandbox.log();
+    let replayed = log
+        .lines()
+        .filter(|l| l.contains("docker run"))
+        .nth(1)
+        .unwrap();
+    for part in [
+        "--cpus 2",
+        "--memory 4g",
+        "--gpus 1",
+        ":/library:ro",
+        "example.org/rosetta:custom score",
+    ] {
+        assert!(replayed.contains(part), "{part}: {replayed}");
+    }
+}
+
+#[test]
+fn env_vars_are_passed_to_container() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+    let env_file = sandbox.path("run.env");
+    std::fs::write(
+        &env_file,
+        "# models\nTORCH_HOME=/models\nHYDRA_FULL_ERROR=0\n",
+    )
+    .unwrap();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "docker", "-w", &work])
+        .args(["--env-file", &env_file])
+        .args(["--env", "OMP_NUM_THREADS=4", "--env", "TORCH_HOME=/weights"])
+        .args(["rfdiffusion"])
+        .assert()
+        .success();
+
+    let log_contents = sandbox.log();
+    for part in [
+        "--env HYDRA_FULL_ERROR=0",
+        "--env OMP_NUM_THREADS=4",
+        "--env TORCH_HOME=/weights",
+    ] {
+        assert!(log_contents.contains(part), "{part}\n{log_contents}");
+    }
+    assert!(!log_contents.contains("TORCH_HOME=/models"));
+}
+
+#[test]
+fn rerun_replays_env_vars() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+    let rc = || {
+        let mut cmd = sandbox.rc();
+        cmd.env_remove("HF_TOKEN");
+        cmd
+    };
+
+    rc().args(["run", "-e", "docker", "-w", &work])
+        .args([
+            "--env",
+            "TORCH_HOME=/weights",
+            "--env",
+            "HF_TOKEN=hf_secret",
+        ])
+        .args(["rfdiffusion"])
+        .assert()
+        .success();
+
+    let recorded = manifest(&sandbox, "0000");
+    assert_eq!(recorded["env"]["TORCH_HOME"], "/weights");
+    assert!(!recorded.to_string().contains("hf_secret"), "{recorded}");
+
+    rc().args(["rerun", "0", "-w", &work])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("secret HF_TOKEN"));
+
+    rc().args(["rerun", "0", "-w", &work])
+        .env("HF_TOKEN", "hf_secret")
+        .assert()
+        .success();
+    let log = sandbox.log();
+    let replayed = log
+        .lines()
+        .filter(|l| l.contains("docker run"))
+        .nth(1)
+        .unwrap();
+    assert!(replayed.contains("--env TORCH_HOME=/weights"), "{replayed}");
+    assert!(replayed.contains("--env HF_TOKEN=hf_secret"), "{replayed}");
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use predicates::prelude::*;
+
+#[test]
+fn doctor_reports_usable_engines_and_apps() {
+    let sandbox = common::Sandbox::new();
+
+    let output = sandbox
+        .rc()
+        .args(["doctor", "--json"])
+        .current_dir(sandbox.dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let checks: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let status = |name: &str| {
+        checks
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == name)
+            .unwrap_or_else(|| panic!("no check named {name}"))["status"]
+            .clone()
+    };
+
+    assert_eq!(status("docker (docker)"), "ok");
+    assert_eq!(status("apptainer (apptainer)"), "ok");
+    assert_eq!(status("engine.default"), "ok");
+    assert_eq!(status("cache root"), "ok");
+    assert_eq!(status("rosetta (apptainer)"), "info");
+}
+
+#[test]
+fn doctor_fails_without_usable_engine() {
+    let sandbox = common::Sandbox::bare();
+
+    sandbox
+        .rc()
+        .arg("doctor")
+        .env("PATH", sandbox.shim.bin_dir())
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "no engine from `engine.priority` is usable",
+        ))
+        .stderr(predicate::str::contains("rc doctor found 1 problem(s)"));
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use predicates::prelude::*;
+
+const NVIDIA_SMI: &str = r#"#!/usr/bin/env bash
+echo "GPU 0: NVIDIA A100-SXM4-80GB (UUID: GPU-0)"
+"#;
+
+fn sandbox(with_gpu: bool) -> common::Sandbox {
+    let sandbox = common::Sandbox::new();
+    if with_gpu {
+        sandbox.shim.install_script("nvidia-smi", NVIDIA_SMI);
+    }
+    sandbox
+}
+
+fn run(sandbox: &common::Sandbox, args: &[&str]) -> assert_cmd::assert::Assert {
+    sandbox
+        .rc()
+        .args(["run", "-w", &sandbox.work_dir()])
+        .args(args)
+        .assert()
+}
+
+#[test]
+fn gpu_app_gets_all_gpus_when_detected() {
+    let gpu = sandbox(true);
+    run(&gpu, &["-e", "docker", "rfdiffusion"]).success();
+    assert!(gpu.log().contains("--gpus all"), "{}", gpu.log());
+
+    run(&gpu, &["-e", "apptainer", "rfdiffusion"]).success();
+    assert!(gpu.log().contains("apptainer run --bind"));
+    assert!(gpu.log().contains(" --nv "), "{}", gpu.log());
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(gpu.dir.path().join("work/.0000.rc.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["gpus"], "all");
+}
+
+#[test]
+fn cpu_app_gets_no_gpu_by_default() {
+    let gpu = sandbox(true);
+    run(&gpu, &["-e", "docker", "rosetta", "score"]).success();
+    assert!(!gpu.log().contains("--gpus"), "{}", gpu.log());
+}
+
+#[test]
+fn gpu_selection_per_engine() {
+    let gpu = sandbox(false);
+    run(
+        &gpu,
+        &["-e", "docker", "--gpus", "device=0,2", "foundry", "rf3"],
+    )
+    .success();
+    assert!(
+        gpu.log().contains(r#"--gpus "device=0,2""#),
+        "{}",
+        gpu.log()
+    );
+
+    run(&gpu, &["-e", "podman", "--gpus", "2", "colabfold"]).success();
+    assert!(
+        gpu.log()
+            .contains("--device nvidia.com/gpu=0 --device nvidia.com/gpu=1"),
+        "{}",
+        gpu.log()
+    );
+
+    run(&gpu, &["-e", "singularity", "--gpus", "1", "openfold3"]).success();
+    assert!(
+        gpu.log().contains("--nv --env CUDA_VISIBLE_DEVICES=0"),
+        "{}",
+        gpu.log()
+    );
+}
+
+#[test]
+fn gpu_app_without_gpu_warns() {
+    let gpu = sandbox(false);
+    run(&gpu, &["-e", "docker", "rfdiffusion"])
+        .success()
+        .stderr(predicate::str::contains("no GPU was detected"));
+    assert!(!gpu.log().contains("--gpus"));
+
+    run(&gpu, &["-e", "docker", "--gpus", "none", "rfdiffusion"])
+        .success()
+        .stderr(predicate::str::contains("`--gpus none` was given"));
+}
+
+#[test]
+fn invalid_gpu_selection_is_rejected() {
+    let gpu = sandbox(false);
+    run(&gpu, &["--gpus", "many", "rfdiffusion"])
+        .failure()
+        .stderr(predicate::str::contains("invalid GPU selection"));
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use predicates::prelude::*;
+
+#[test]
+fn history_lists_filters_and_shows_runs() {
+    let sandbox = common::Sandbox::new();
+    let work = &sandbox.work_dir();
+    let rc = || sandbox.rc();
+
+    rc().args([
+        "run",
+        "-e",
+        "docker",
+        "-w",
+        work,
+        "--tag",
+        "baseline",
+        "--note",
+        "first try",
+        "rosetta",
+        "score",
+    ])
+    .assert()
+    .success();
+    rc().args(["run", "-e", "docker", "-w", work, "score"])
+        .assert()
+        .success();
+
+    let output = rc()
+        .args(["history", "--json", "-w", work])
+        .output()
+        .unwrap();
+    let runs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(runs.as_array().unwrap().len(), 2);
+    assert_eq!(runs[1]["app"], "Score");
+
+    let output = rc()
+        .args(["history", "--json", "--tag", "baseline", "-w", work])
+        .output()
+        .unwrap();
+    let runs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(runs.as_array().unwrap().len(), 1);
+    assert_eq!(runs[0]["id"], 0);
+    assert_eq!(runs[0]["note"], "first try");
+
+    let output = rc()
+        .args(["history", "--json", "--status", "failed", "-w", work])
+        .output()
+        .unwrap();
+    let runs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(runs.as_array().unwrap().is_empty());
+
+    rc().args(["history", "show", "0", "-w", work])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("first try"))
+        .stdout(predicate::str::contains("docker"))
+        .stdout(predicate::str::contains("score"));
+
+    rc().args(["history", "show", "5", "-w", work])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("run 5 not found"));
+}
@@ -1,3 +1,211 @@
 mod common;
 
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
 // todo add tests for hpc specifics: `rc build` and `rc clean`
+
+const IMAGE: &str = "hpc/rosettacommons-rosetta:serial.sif";
+
+/// `singularity pull` that fails halfway through, after writing part of the image.
+const FAILING_PULL: &str = r#"#!/usr/bin/env bash
+echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+printf 'partial' > "$2"
+exit 1
+"#;
+
+/// `singularity pull` that takes a while, so concurrent installs overlap.
+const SLOW_PULL: &str = r#"#!/usr/bin/env bash
+echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+sleep 1
+printf '#!/usr/bin/env run-singularity\n\0SIF_MAGIC\0' > "$2"
+"#;
+
+/// `singularity pull` honoring a private umask.
+const PRIVATE_PULL: &str = r#"#!/usr/bin/env bash
+printf '#!/usr/bin/env run-singularity\n\0SIF_MAGIC\0' > "$2"
+chmod 600 "$2"
+"#;
+
+/// `rc install rosetta -e singularity`.
+const INSTALL: [&str; 4] = ["install", "rosetta", "-e", "singularity"];
+
+/// [`INSTALL`] against the sandbox, for tests that spawn it themselves.
+fn install(hpc: &common::Sandbox) -> std::process::Command {
+    let mut cmd = hpc.command();
+    cmd.args(INSTALL);
+    cmd
+}
+
+fn cache_files(hpc: &common::Sandbox) -> Vec<String> {
+    let mut files: Vec<String> = std::fs::read_dir(hpc.dir.child("cache/hpc").path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    files
+}
+
+fn pulls(hpc: &common::Sandbox) -> usize {
+    hpc.log()
+        .lines()
+        .filter(|l| l.contains("singularity pull"))
+        .count()
+}
+
+#[test]
+fn image_is_pulled_into_place_atomically() {
+    let hpc = common::Sandbox::new();
+    hpc.rc().args(INSTALL).assert().success();
+
+    assert_eq!(
+        cache_files(&hpc),
+        [
+            "rosettacommons-rosetta:serial.sif",
+            "rosettacommons-rosetta:serial.sif.lock"
+        ]
+    );
+    let log = hpc.log();
+    assert!(
+        log.contains(".tmp docker://rosettacommons/rosetta:serial"),
+        "{log}"
+    );
+
+    // an existing image is reused
+    hpc.rc().args(INSTALL).assert().success();
+    assert_eq!(pulls(&hpc), 1);
+}
+
+#[test]
+fn failed_pull_leaves_no_image_behind() {
+    let hpc = common::Sandbox::new();
+    hpc.shim.install_script("singularity", FAILING_PULL);
+
+    hpc.rc()
+        .args(INSTALL)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to build"));
+
+    assert_eq!(
+        cache_files(&hpc),
+        ["rosettacommons-rosetta:serial.sif.lock"]
+    );
+}
+
+#[test]
+fn invalid_image_is_rejected() {
+    let hpc = common::Sandbox::new();
+    hpc.shim.install_script(
+        "singularity",
+        "#!/usr/bin/env bash\nprintf 'not an image' > \"$2\"\n",
+    );
+
+    hpc.rc()
+        .args(INSTALL)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is not a valid SIF image"));
+    assert!(!hpc.dir.child(format!("cache/{IMAGE}")).exists());
+}
+
+#[test]
+fn partial_builds_of_interrupted_runs_are_removed() {
+    let hpc = common::Sandbox::new();
+    let partial = hpc.dir.child(format!("cache/{IMAGE}.node7.4242.tmp"));
+    partial.write_str("partial").unwrap();
+
+    hpc.rc().args(INSTALL).assert().success();
+
+    assert!(!partial.exists());
+    assert!(hpc.dir.child(format!("cache/{IMAGE}")).exists());
+}
+
+#[test]
+fn concurrent_installs_pull_once() {
+    let hpc = common::Sandbox::new();
+    hpc.shim.install_script("singularity", SLOW_PULL);
+
+    let children: Vec<_> = (0..3)
+        .map(|_| {
+            install(&hpc)
+                .stdout(std::process::Stdio::null())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+
+    assert_eq!(pulls(&hpc), 1);
+    assert_eq!(
+        cache_files(&hpc),
+        [
+            "rosettacommons-rosetta:serial.sif",
+            "rosettacommons-rosetta:serial.sif.lock"
+        ]
+    );
+}
+
+#[test]
+fn shared_image_is_used_before_the_cache() {
+    let hpc = common::Sandbox::new();
+    let shared = hpc.dir.child("shared");
+    shared.child(IMAGE).write_str("shared image").unwrap();
+    let work_dir = hpc.dir.child("work");
+    work_dir.create_dir_all().unwrap();
+
+    let mut cmd = hpc.command();
+    cmd.args(["run", "-e", "singularity", "rosetta", "score"])
+        .current_dir(work_dir.path())
+        .env("RC_SHARED_ROOT", shared.path());
+    assert_cmd::Command::from_std(cmd).assert().success();
+
+    let log = hpc.log();
+    assert_eq!(pulls(&hpc), 0);
+    assert!(
+        log.contains(&format!("{} score", shared.child(IMAGE).path().display())),
+        "{log}"
+    );
+}
+
+#[test]
+fn shared_install_writes_group_readable_image() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let hpc = common::Sandbox::new();
+    hpc.shim.install_script("singularity", PRIVATE_PULL);
+    let shared = hpc.dir.child("shared");
+    shared.child("hpc").create_dir_all().unwrap();
+    std::fs::set_permissions(
+        shared.child("hpc").path(),
+        std::fs::Permissions::from_mode(0o700),
+    )
+    .unwrap();
+
+    let mut cmd = install(&hpc);
+    cmd.arg("--shared").env("RC_SHARED_ROOT", shared.path());
+    assert_cmd::Command::from_std(cmd).assert().success();
+
+    assert!(!hpc.dir.child(format!("cache/{IMAGE}")).exists());
+    for path in [
+        shared.child("hpc").path().to_owned(),
+        shared.child(IMAGE).path().to_owned(),
+    ] {
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(
+            mode & 0o040,
+            0o040,
+            "{} is not group-readable",
+            path.display()
+        );
+    }
+
+    let mut cmd = install(&hpc);
+    cmd.arg("--shared");
+    assert_cmd::Command::from_std(cmd)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("`--shared` needs `shared.root`"));
+}
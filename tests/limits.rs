@@ -0,0 +1,66 @@
+mod common;
+
+use predicates::prelude::*;
+
+fn run(engine: &str, extra: &[&str]) -> (String, serde_json::Value, assert_cmd::assert::Assert) {
+    let sandbox = common::Sandbox::new();
+    sandbox.shim.install("pixi");
+    let work = sandbox.work_dir();
+
+    let assert = sandbox
+        .rc()
+        .args(["run", "-e", engine, "-w", &work])
+        .args(extra)
+        .args(["rosetta", "score"])
+        .assert()
+        .success();
+
+    let manifest = std::fs::read_to_string(sandbox.dir.path().join("work/.0000.rc.json")).unwrap();
+    let run_log = std::fs::read_to_string(sandbox.dir.path().join("work/.0000.rc.log")).unwrap();
+    (
+        sandbox.log() + &run_log,
+        serde_json::from_str(&manifest).unwrap(),
+        assert,
+    )
+}
+
+#[test]
+fn docker_limits() {
+    let (log, manifest, _) = run(
+        "docker",
+        &["--cpus", "2.5", "--memory", "16g", "--shm-size", "8g"],
+    );
+    assert!(
+        log.contains("--cpus 2.5 --memory 16g --shm-size 8g"),
+        "{log}"
+    );
+    assert_eq!(manifest["limits"]["cpus"], 2.5);
+    assert_eq!(manifest["limits"]["memory"], "16g");
+    assert_eq!(manifest["limits"]["shm_size"], "8g");
+}
+
+#[test]
+fn apptainer_limits_ignore_shm_size() {
+    let (log, _, assert) = run("apptainer", &["--cpus", "4", "--shm-size", "8g"]);
+    assert!(log.contains("--cpus 4"), "{log}");
+    assert!(!log.contains("--shm-size"), "{log}");
+    assert.stderr(predicate::str::contains("ignoring --shm-size"));
+}
+
+#[test]
+fn native_limits_set_thread_counts() {
+    let (log, _, assert) = run("none", &["--cpus", "1.5", "--memory", "4g"]);
+    assert!(log.contains("OMP_NUM_THREADS=2"), "{log}");
+    assert!(log.contains("OPENBLAS_NUM_THREADS=2"), "{log}");
+    assert.stderr(predicate::str::contains("do not support --memory"));
+}
+
+#[test]
+fn invalid_limits_are_rejected() {
+    common::Sandbox::new()
+        .rc()
+        .args(["run", "--memory", "lots", "rosetta"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid size `lots`"));
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+const DIGEST: &str = "sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+/// Docker shim that logs invocations and reports a registry digest on `docker image inspect`.
+const DOCKER_SHIM: &str = r#"#!/usr/bin/env bash
+echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+if [ "$1 $2" = "image inspect" ]; then
+    echo '["rosettacommons/rosetta@sha256:0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"]'
+fi
+exit 0
+"#;
+
+#[test]
+fn lock_pins_images_for_docker_and_hpc_runs() {
+    let sandbox = common::Sandbox::new();
+    sandbox.shim.install_script("docker", DOCKER_SHIM);
+    let work = sandbox.work_dir();
+
+    sandbox
+        .rc()
+        .args(["lock", "rosetta", "-w", &work])
+        .assert()
+        .success();
+
+    sandbox
+        .dir
+        .child("work/rc.lock")
+        .assert(predicate::str::contains(DIGEST));
+
+    for engine in ["docker", "singularity"] {
+        sandbox
+            .rc()
+            .args(["run", "-e", engine, "-w", &work, "rosetta", "score"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Using image pinned by rc.lock"));
+    }
+
+    let log_contents = sandbox.log();
+    for part in [
+        format!(" rosettacommons/rosetta@{DIGEST} score"),
+        format!(" docker://rosettacommons/rosetta@{DIGEST}"),
+        format!("rosettacommons-rosetta@{DIGEST}.sif score"),
+    ] {
+        assert!(
+            log_contents.contains(&part),
+            "Expected log to contain:\n  {part}\n\nActual log contents:\n{log_contents}"
+        );
+    }
+}
+
+#[test]
+fn image_and_tag_overrides() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+
+    sandbox
+        .rc()
+        .env("RC_ROSETTA_TAG", "mpi")
+        .args(["run", "-e", "docker", "-w", &work, "rosetta", "score"])
+        .assert()
+        .success();
+
+    sandbox
+        .rc()
+        .args([
+            "run",
+            "-e",
+            "docker",
+            "--image",
+            "example.org/rosetta:custom",
+        ])
+        .args(["-w", &work, "rosetta", "score"])
+        .assert()
+        .success();
+
+    let log_contents = sandbox.log();
+    for part in [
+        " rosettacommons/rosetta:mpi score",
+        " example.org/rosetta:custom score",
+    ] {
+        assert!(
+            log_contents.contains(part),
+            "Expected log to contain:\n  {part}\n\nActual log contents:\n{log_contents}"
+        );
+    }
+}
+
+#[test]
+fn lock_with_invalid_digest_is_rejected() {
+    let sandbox = common::Sandbox::new();
+    let work = sandbox.work_dir();
+    let work_dir = sandbox.dir.child("work");
+    work_dir
+        .child("rc.lock")
+        .write_str(
+            "[apps.rosetta]\nimage = \"rosettacommons/rosetta:serial\"\ndigest = \"sha256:x; touch pwned\"\n",
+        )
+        .unwrap();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "docker", "-w", &work, "rosetta", "score"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid digest"));
+    work_dir.child("pwned").assert(predicate::path::missing());
+}
@@ -29,6 +29,35 @@ fn no_run_no_app() {
         ));
 }
 
+#[test]
+fn completions_include_apps_and_engines() {
+    for shell in ["bash", "zsh", "fish"] {
+        cargo_bin_cmd!()
+            .args(["completions", shell])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("proteinmpnnscript"))
+            .stdout(predicate::str::contains("apptainer"));
+    }
+}
+
+#[test]
+fn man_page() {
+    cargo_bin_cmd!()
+        .arg("man")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".TH rc 1"));
+
+    let dir = assert_fs::TempDir::new().unwrap();
+    cargo_bin_cmd!()
+        .args(["man", "--out-dir", dir.path().to_str().unwrap()])
+        .assert()
+        .success();
+    assert!(dir.path().join("rc.1").exists());
+    assert!(dir.path().join("rc-run.1").exists());
+}
+
 #[test]
 fn container_shim_test() {
     let fixture = common::ContainerPathShim::new();
@@ -0,0 +1,154 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+/// Sandbox with `work`, `library` and `db` directories, relative mounts resolve inside it.
+fn sandbox() -> common::Sandbox {
+    let sandbox = common::Sandbox::new();
+    for dir in ["work", "library", "db"] {
+        sandbox.dir.child(dir).create_dir_all().unwrap();
+    }
+    sandbox
+}
+
+fn run(f: &common::Sandbox, engine: &str, extra: &[&str]) -> assert_cmd::assert::Assert {
+    run_with_args(f, engine, extra, &[])
+}
+
+fn run_with_args(
+    f: &common::Sandbox,
+    engine: &str,
+    extra: &[&str],
+    app_args: &[&str],
+) -> assert_cmd::assert::Assert {
+    f.rc()
+        .args(["run", "-e", engine, "-w", &f.path("work")])
+        .args(extra)
+        .args(["rosetta", "score"])
+        .args(app_args)
+        .current_dir(f.dir.path())
+        .assert()
+}
+
+#[test]
+fn mounts_are_passed_to_docker_and_hpc() {
+    let f = sandbox();
+    let mounts = ["--mount", "library:/library:ro", "--mount", &f.path("db")];
+
+    run(&f, "docker", &mounts).success();
+    let log = f.log();
+    assert!(
+        log.contains(&format!("--volume {}:/library:ro", f.path("library"))),
+        "{log}"
+    );
+    assert!(
+        log.contains(&format!("--volume {0}:{0}", f.path("db"))),
+        "{log}"
+    );
+
+    run(&f, "apptainer", &mounts).success();
+    let log = f.log();
+    assert!(
+        log.contains(&format!("--bind {}:/library:ro", f.path("library"))),
+        "{log}"
+    );
+    assert!(
+        log.contains(&format!("--bind {0}:{0}", f.path("db"))),
+        "{log}"
+    );
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(f.dir.path().join("work/.0000.rc.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        manifest["mounts"][1],
+        format!("{}:/library:ro", f.path("library"))
+    );
+}
+
+#[test]
+fn command_line_mount_overrides_project_mount() {
+    let f = sandbox();
+    f.dir
+        .child("work/.rc.toml")
+        .write_str("mounts = [\"/nonexistent:/library\"]\n")
+        .unwrap();
+
+    run(&f, "docker", &["--mount", "library:/library:ro"]).success();
+    assert!(!f.log().contains("/nonexistent"), "{}", f.log());
+}
+
+#[test]
+fn missing_mount_source_is_an_error() {
+    let f = sandbox();
+    run(&f, "docker", &["--mount", "missing:/missing"])
+        .failure()
+        .stderr(predicate::str::contains("does not exist"));
+
+    run(&f, "docker", &["--mount", "library:relative"])
+        .failure()
+        .stderr(predicate::str::contains("container path must be absolute"));
+
+    run(&f, "docker", &["--mount", "library:/w"])
+        .failure()
+        .stderr(predicate::str::contains("working directory"));
+}
+
+#[test]
+fn argument_paths_are_auto_mounted() {
+    let f = sandbox();
+    f.dir.child("library/1abc.pdb").write_str("ATOM").unwrap();
+    let pdb = f.dir.path().join("library/1abc.pdb");
+    let pdb = pdb.to_str().unwrap();
+
+    run_with_args(&f, "docker", &[], &["-in:file:s", pdb]).success();
+    let log = f.log();
+    assert!(
+        log.contains(&format!("--volume {}:/rc/mnt/0:ro", f.path("library"))),
+        "{log}"
+    );
+    assert!(log.contains("-in:file:s /rc/mnt/0/1abc.pdb"), "{log}");
+
+    let run_log = std::fs::read_to_string(f.dir.path().join("work/.0000.rc.log")).unwrap();
+    assert!(
+        run_log.contains(&format!("# rewrote argument {pdb} -> /rc/mnt/0/1abc.pdb")),
+        "{run_log}"
+    );
+
+    let manifest: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(f.dir.path().join("work/.0000.rc.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(manifest["inputs"][0]["path"], pdb);
+    assert_eq!(manifest["rewrites"][0]["to"], "/rc/mnt/0/1abc.pdb");
+
+    run_with_args(&f, "docker", &["--no-auto-mount"], &["-in:file:s", pdb]).success();
+    assert!(f.log().contains(&format!("-in:file:s {pdb}")));
+}
+
+#[test]
+fn history_shows_the_command_of_auto_mounted_runs() {
+    let f = sandbox();
+    f.dir.child("library/1abc.pdb").write_str("ATOM").unwrap();
+    let pdb = f.dir.path().join("library/1abc.pdb");
+
+    run_with_args(&f, "docker", &[], &["-in:file:s", pdb.to_str().unwrap()]).success();
+
+    let output = f
+        .rc()
+        .args(["history", "show", "0", "-w", &f.path("work")])
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    let command = output
+        .lines()
+        .find(|l| l.contains('$'))
+        .unwrap_or_else(|| panic!("no command line in:\n{output}"));
+    assert!(command.contains("docker run "), "{output}");
+    assert!(!command.contains("# rewrote"), "{output}");
+}
@@ -0,0 +1,239 @@
+mod common;
+
+use assert_fs::prelude::*;
+
+/// `pixi` shim that logs the working directory and each argument of `pixi run` on its own line.
+/// `pixi run setup` creates the repo clone the LigandMPNN recipe would.
+const PIXI_SHIM: &str = r#"#!/usr/bin/env bash
+if [ "$1 $2" = "run setup" ]; then
+  mkdir -p ligandmpnn-repo-clone
+  exit 0
+fi
+echo "cwd: $(pwd)" >> "$TEST_INVOCATIONS_LOG"
+printf 'arg: %s\n' "$@" >> "$TEST_INVOCATIONS_LOG"
+"#;
+
+/// Program of an existing installation, logging like the `pixi` shim.
+const TOOL: &str = r#"#!/usr/bin/env bash
+echo "cwd: $(pwd)" >> "$TEST_INVOCATIONS_LOG"
+echo "CONDA_PREFIX: $CONDA_PREFIX" >> "$TEST_INVOCATIONS_LOG"
+printf 'arg: %s\n' "$0" "$@" >> "$TEST_INVOCATIONS_LOG"
+"#;
+
+/// Sandbox with the `pixi` shim and no container engines.
+fn sandbox() -> common::Sandbox {
+    let sandbox = common::Sandbox::bare();
+    sandbox.shim.install_script("pixi", PIXI_SHIM);
+    sandbox
+}
+
+fn run(app: &str, args: &[&str]) -> (common::Sandbox, String) {
+    let sandbox = sandbox();
+    let log = run_in(&sandbox, app, args);
+    (sandbox, log)
+}
+
+/// Run `app` natively in `sandbox`, returning the log of the programs it started.
+fn run_in(sandbox: &common::Sandbox, app: &str, args: &[&str]) -> String {
+    sandbox
+        .rc()
+        .args(["run", "-e", "none", "-w", &sandbox.work_dir()])
+        .arg(app)
+        .args(args)
+        .assert()
+        .success();
+
+    sandbox.log()
+}
+
+/// Directory of an existing installation with `program` in its `bin`.
+fn install(sandbox: &common::Sandbox, prefix: &str, program: &str) -> String {
+    let bin = sandbox.dir.child(prefix).child("bin");
+    bin.create_dir_all().unwrap();
+    bin.child(program).write_str(TOOL).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let path = bin.child(program).to_path_buf();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    sandbox.path(prefix)
+}
+
+#[test]
+fn arguments_are_passed_verbatim() {
+    let (sandbox, log) = run(
+        "rosetta",
+        &["score", "-in:file:s", "my model's.pdb", "$HOME;ls"],
+    );
+    let work = sandbox.dir.child("work").path().canonicalize().unwrap();
+
+    assert!(
+        log.starts_with(&format!("cwd: {}\n", work.display())),
+        "{log}"
+    );
+    assert!(
+        log.contains("arg: --executable\narg: --\narg: score\narg: -in:file:s\narg: my model's.pdb\narg: $HOME;ls\n"),
+        "{log}"
+    );
+    assert!(!log.contains("arg: execute"), "{log}");
+}
+
+#[test]
+fn recipe_variables_and_working_directory_are_resolved() {
+    let (sandbox, log) = run("ligandmpnn", &["--seed", "7"]);
+    let env = sandbox
+        .dir
+        .child("cache/native/Ligandmpnn")
+        .path()
+        .canonicalize()
+        .unwrap();
+
+    assert!(
+        log.starts_with(&format!("cwd: {}/ligandmpnn-repo-clone\n", env.display())),
+        "{log}"
+    );
+    assert!(
+        log.contains(&format!(
+            "arg: python\narg: run.py\narg: --checkpoint_protein_mpnn\narg: {}/ligandmpnn-repo-clone/model_params/proteinmpnn_v_48_020.pt\n",
+            env.display()
+        )),
+        "{log}"
+    );
+}
+
+#[test]
+fn existing_installation_is_used_without_pixi() {
+    let sandbox = sandbox();
+    let prefix = install(&sandbox, "rosetta", "score");
+    sandbox
+        .dir
+        .child("rosetta/conda-meta")
+        .create_dir_all()
+        .unwrap();
+    sandbox
+        .dir
+        .child("config.toml")
+        .write_str(&format!("[apps.rosetta]\nprefix = \"{prefix}\"\n"))
+        .unwrap();
+
+    let log = run_in(
+        &sandbox,
+        "rosetta",
+        &[
+            "score",
+            "-in:file:s",
+            "a b.pdb",
+            "-out:prefix",
+            "$HOME/${run}",
+        ],
+    );
+    let work = sandbox.dir.child("work").path().canonicalize().unwrap();
+
+    assert!(
+        log.starts_with(&format!("cwd: {}\n", work.display())),
+        "{log}"
+    );
+    assert!(log.contains(&format!("CONDA_PREFIX: {prefix}\n")), "{log}");
+    // variables outside the installation are left to the app
+    assert!(
+        log.contains(&format!(
+            "arg: {prefix}/bin/score\narg: -in:file:s\narg: a b.pdb\narg: -out:prefix\narg: $HOME/${{run}}\n"
+        )),
+        "{log}"
+    );
+    assert!(
+        !sandbox.dir.child("cache/native").exists(),
+        "no Pixi environment"
+    );
+
+    let manifest = std::fs::read_to_string(sandbox.dir.child("work/.0000.rc.json").path()).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    assert_eq!(manifest["native_prefix"], prefix.as_str());
+    assert!(manifest["pixi_recipe_hash"].is_null());
+}
+
+#[test]
+fn existing_checkout_is_the_app_directory() {
+    let sandbox = sandbox();
+    let checkout = install(&sandbox, "LigandMPNN", "python");
+    let weights = format!("{checkout}/model_params");
+    sandbox
+        .dir
+        .child("config.toml")
+        .write_str(&format!("[apps.ligandmpnn]\nprefix = \"{checkout}\"\n"))
+        .unwrap();
+
+    let log = run_in(
+        &sandbox,
+        "ligandmpnn",
+        &[
+            "--env",
+            &format!("LIGANDMPNN_WEIGHTS={weights}"),
+            "--seed",
+            "7",
+        ],
+    );
+    let checkout_dir = sandbox
+        .dir
+        .child("LigandMPNN")
+        .path()
+        .canonicalize()
+        .unwrap();
+
+    assert!(
+        log.starts_with(&format!("cwd: {}\n", checkout_dir.display())),
+        "{log}"
+    );
+    assert!(
+        log.contains(&format!(
+            "arg: run.py\narg: --checkpoint_protein_mpnn\narg: {weights}/proteinmpnn_v_48_020.pt\n"
+        )),
+        "{log}"
+    );
+}
+
+#[test]
+fn existing_checkout_without_recipe_variables_is_an_error() {
+    let sandbox = sandbox();
+    let checkout = install(&sandbox, "LigandMPNN", "python");
+    sandbox
+        .dir
+        .child("config.toml")
+        .write_str(&format!("[apps.ligandmpnn]\nprefix = \"{checkout}\"\n"))
+        .unwrap();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "none", "-w", &sandbox.work_dir()])
+        .args(["ligandmpnn", "--seed", "7"])
+        .env_remove("LIGANDMPNN_WEIGHTS")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "does not define $LIGANDMPNN_WEIGHTS",
+        ));
+    sandbox
+        .dir
+        .child("invocations.log")
+        .assert(predicates::path::missing());
+}
+
+#[test]
+fn run_id_is_reserved_before_the_app_starts() {
+    let sandbox = common::Sandbox::bare();
+    sandbox.shim.install_script(
+        "pixi",
+        r#"#!/usr/bin/env bash
+if [ "$1 $2" = "run setup" ]; then exit 0; fi
+test -e .0000.rc.log && echo "log reserved" >> "$TEST_INVOCATIONS_LOG"
+echo "warning from the app" >&2
+"#,
+    );
+
+    run_in(&sandbox, "rosetta", &["score"]);
+
+    assert_eq!(sandbox.log(), "log reserved\n");
+    let log = std::fs::read_to_string(sandbox.dir.child("work/.0000.rc.log").path()).unwrap();
+    assert_eq!(log.matches("warning from the app").count(), 1, "{log}");
+}
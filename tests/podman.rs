@@ -0,0 +1,99 @@
+mod common;
+
+fn assert_contains(log: &str, part: &str) {
+    assert!(
+        log.contains(part),
+        "Expected log to contain command line part:\n  {part}\n\nActual log contents:\n{log}"
+    );
+}
+
+#[test]
+fn mock_podman_rosetta_score() {
+    let sandbox = common::Sandbox::bare();
+    sandbox.shim.install("podman");
+    let work = sandbox.work_dir();
+
+    sandbox
+        .rc()
+        .args(["run", "-e", "podman", "-w", &work, "rosetta", "score"])
+        .assert()
+        .success();
+
+    let log = sandbox.log();
+    assert_contains(&log, "podman image exists docker.io/rosettacommons/rosetta");
+    assert_contains(&log, "podman run --rm --userns=keep-id");
+    assert_contains(&log, &format!("--volume {work}:/w:z"));
+    assert_contains(&log, "--workdir /w");
+    assert_contains(&log, " docker.io/rosettacommons/rosetta:serial score");
+    assert!(!log.contains("--user "), "{log}");
+
+    let manifest = std::fs::read_to_string(sandbox.dir.path().join("work/.0000.rc.json")).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    assert_eq!(manifest["engine"], "podman");
+}
+
+#[test]
+fn only_the_working_directory_is_relabeled_by_default() {
+    let sandbox = common::Sandbox::bare();
+    sandbox.shim.install("podman");
+    let work = sandbox.work_dir();
+    let refs = sandbox.path("refs");
+    std::fs::create_dir(&refs).unwrap();
+
+    for relabel in ["false", "true"] {
+        sandbox
+            .rc()
+            .env("RC_PODMAN_RELABEL_MOUNTS", relabel)
+            .args(["run", "-e", "podman", "-w", &work, "--mount"])
+            .arg(format!("{refs}:/refs:ro"))
+            .args(["rosetta", "score"])
+            .assert()
+            .success();
+    }
+
+    let log = sandbox.log();
+    let runs: Vec<_> = log.lines().filter(|l| l.contains("podman run")).collect();
+    assert_eq!(runs.len(), 2, "{log}");
+    assert_contains(
+        runs[0],
+        &format!("--volume {work}:/w:z --volume {refs}:/refs:ro "),
+    );
+    assert_contains(
+        runs[1],
+        &format!("--volume {work}:/w:z --volume {refs}:/refs:ro,z "),
+    );
+}
+
+#[test]
+fn podman_install_and_clean() {
+    let sandbox = common::Sandbox::bare();
+    sandbox.shim.install("podman");
+
+    for command in ["install", "clean"] {
+        sandbox
+            .rc()
+            .args([command, "rfdiffusion", "-e", "podman"])
+            .assert()
+            .success();
+    }
+
+    let log = sandbox.log();
+    assert_contains(
+        &log,
+        "podman image exists docker.io/rosettacommons/rfdiffusion",
+    );
+    assert_contains(
+        &log,
+        "podman image rm --force --ignore docker.io/rosettacommons/rfdiffusion",
+    );
+}
+
+#[test]
+fn clean_without_engine_skips_missing_podman() {
+    let sandbox = common::Sandbox::bare();
+    sandbox.shim.install("docker");
+
+    sandbox.rc().args(["clean", "rosetta"]).assert().success();
+
+    assert_contains(&sandbox.log(), "docker image rm -f rosettacommons/rosetta");
+}
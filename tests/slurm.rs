@@ -0,0 +1,253 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+/// `sbatch` shim that logs its invocation together with the submitted script.
+const SBATCH_SHIM: &str = r#"#!/usr/bin/env bash
+echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+cat "$1" >> "$TEST_INVOCATIONS_LOG"
+echo "Submitted batch job 4242"
+"#;
+
+fn sandbox() -> common::Sandbox {
+    let slurm = common::Sandbox::new();
+    slurm.shim.install_script("sbatch", SBATCH_SHIM);
+    slurm
+}
+
+/// `rc submit` from the sandbox's working directory.
+fn submit(slurm: &common::Sandbox, args: &[&str]) -> assert_cmd::Command {
+    let mut cmd = slurm.rc();
+    cmd.arg("submit").args(["-w", &slurm.work_dir()]).args(args);
+    cmd
+}
+
+#[test]
+fn print_shows_the_script_without_submitting() {
+    let slurm = sandbox();
+    let work = slurm.work_dir();
+
+    submit(
+        &slurm,
+        &[
+            "--print",
+            "-p",
+            "gpu",
+            "-t",
+            "2:00:00",
+            "-e",
+            "apptainer",
+            "--cpus",
+            "8",
+            "--memory",
+            "16g",
+            "rfdiffusion",
+            "inference.num_designs=2",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("#!/bin/bash\n"))
+        .stdout(predicate::str::contains("#SBATCH --job-name=rc-rfdiffusion\n"))
+        .stdout(predicate::str::contains(format!("#SBATCH --chdir={work}\n")))
+        .stdout(predicate::str::contains(format!(
+            "#SBATCH --output={work}/.0000.rc.%j.out\n"
+        )))
+        .stdout(predicate::str::contains("#SBATCH --partition=gpu\n"))
+        .stdout(predicate::str::contains("#SBATCH --time=2:00:00\n"))
+        .stdout(predicate::str::contains("#SBATCH --cpus-per-task=8\n"))
+        .stdout(predicate::str::contains("#SBATCH --mem=16384M\n"))
+        .stdout(predicate::str::contains("#SBATCH --gpus=1\n"))
+        .stdout(predicate::str::contains(format!(
+            " run -e apptainer -w {work} --gpus all --cpus 8 --memory 16g rfdiffusion inference.num_designs=2\n"
+        )));
+
+    assert_eq!(slurm.log(), "");
+    assert!(!slurm.dir.child("work/.0000.rc.sbatch").exists());
+}
+
+#[test]
+fn submit_prepares_the_image_and_keeps_the_script() {
+    let slurm = sandbox();
+    slurm
+        .dir
+        .child("config.toml")
+        .write_str("[slurm]\npartition = \"short\"\n")
+        .unwrap();
+
+    submit(
+        &slurm,
+        &[
+            "-e",
+            "singularity",
+            "rosetta",
+            "score",
+            "-in:file:s",
+            "1abc.pdb",
+        ],
+    )
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Submitted batch job 4242"));
+
+    let script = slurm.dir.child("work/.0000.rc.sbatch");
+    script.assert(predicate::str::contains("#SBATCH --partition=short\n"));
+    script.assert(predicate::str::contains("#SBATCH --gpus").not());
+
+    // the image is pulled once, before the job is submitted
+    let log = slurm.log();
+    let pull = log.find("singularity pull").expect("image was not pulled");
+    let sbatch = log.find("sbatch ").expect("job was not submitted");
+    assert!(pull < sbatch, "{log}");
+    assert!(log.contains(".0000.rc.sbatch"), "{log}");
+    assert!(
+        slurm
+            .dir
+            .child("cache/hpc/rosettacommons-rosetta:serial.sif")
+            .exists()
+    );
+}
+
+#[test]
+fn array_tasks_get_their_index() {
+    let slurm = sandbox();
+    let work = slurm.work_dir();
+
+    submit(
+        &slurm,
+        &[
+            "--print",
+            "--array",
+            "1-10%2",
+            "-e",
+            "docker",
+            "--tag",
+            "screen",
+            "rosetta",
+            "score",
+            "-in:file:s",
+            "inputs/model_{task}.pdb",
+        ],
+    )
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("#SBATCH --array=1-10%2\n"))
+    .stdout(predicate::str::contains(format!(
+        "#SBATCH --output={work}/.0000.rc.%A_%a.out\n"
+    )))
+    .stdout(predicate::str::contains(
+        "--tag screen rosetta score '-in:file:s' inputs/model_\"$SLURM_ARRAY_TASK_ID\".pdb\n",
+    ));
+}
+
+#[test]
+fn submitted_script_reserves_its_run_id() {
+    let slurm = sandbox();
+
+    for _ in 0..2 {
+        submit(&slurm, &["-e", "docker", "rosetta", "score"])
+            .assert()
+            .success();
+    }
+
+    slurm
+        .dir
+        .child("work/.0000.rc.sbatch")
+        .assert(predicate::path::exists());
+    slurm
+        .dir
+        .child("work/.0001.rc.sbatch")
+        .assert(predicate::path::exists());
+}
+
+#[test]
+fn job_records_its_run_under_the_script_id() {
+    let slurm = sandbox();
+    submit(&slurm, &["-e", "docker", "rosetta", "score"])
+        .assert()
+        .success();
+    let log = slurm.log();
+    assert!(log.contains(" --run-id 0 rosetta score\n"), "{log}");
+
+    // what the job runs on the compute node
+    slurm
+        .rc()
+        .args([
+            "run",
+            "-e",
+            "docker",
+            "-w",
+            &slurm.work_dir(),
+            "--run-id",
+            "0",
+        ])
+        .args(["rosetta", "score"])
+        .assert()
+        .success();
+
+    let work = slurm.dir.child("work");
+    work.child(".0000.rc.log")
+        .assert(predicate::str::contains("docker run"));
+    work.child(".0000.rc.json")
+        .assert(predicate::path::exists());
+    work.child(".0001.rc.log")
+        .assert(predicate::path::missing());
+}
+
+#[test]
+fn array_tasks_take_their_own_run_ids() {
+    let slurm = sandbox();
+    submit(
+        &slurm,
+        &["--array", "1-4", "-e", "docker", "rosetta", "score"],
+    )
+    .assert()
+    .success();
+    assert!(!slurm.log().contains("--run-id"), "{}", slurm.log());
+
+    let work = slurm.dir.child("work");
+    work.child(".0000.rc.sbatch")
+        .assert(predicate::path::exists());
+    // the id is held by the script alone, the tasks log under the next ones
+    work.child(".0000.rc.log")
+        .assert(predicate::path::missing());
+}
+
+#[test]
+fn secrets_reach_the_job_without_being_written_to_the_script() {
+    let slurm = sandbox();
+    slurm.shim.install_script(
+        "sbatch",
+        &SBATCH_SHIM.replace(
+            "cat \"$1\"",
+            "echo \"job HF_TOKEN=$HF_TOKEN\" >> \"$TEST_INVOCATIONS_LOG\"\ncat \"${@: -1}\"",
+        ),
+    );
+
+    submit(
+        &slurm,
+        &[
+            "-e",
+            "docker",
+            "--env",
+            "HF_TOKEN=hf_secret",
+            "--env",
+            "HYDRA_FULL_ERROR=1",
+            "rosetta",
+            "score",
+        ],
+    )
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("hf_secret").not());
+
+    let script = slurm.dir.child("work/.0000.rc.sbatch");
+    script.assert(predicate::str::contains(
+        " --env HF_TOKEN --env HYDRA_FULL_ERROR=1 ",
+    ));
+    script.assert(predicate::str::contains("hf_secret").not());
+
+    let log = slurm.log();
+    assert!(log.contains("sbatch --export=ALL "), "{log}");
+    assert!(log.contains("job HF_TOKEN=hf_secret\n"), "{log}");
+}
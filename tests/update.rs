@@ -0,0 +1,141 @@
+mod common;
+
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+
+/// `docker` shim backed by two files: `image pull` copies the upstream digest into the local one,
+/// `image inspect` reports the local digest.
+const DOCKER_SHIM: &str = r#"#!/usr/bin/env bash
+echo "$0 $@" >> "$TEST_INVOCATIONS_LOG"
+case "$1 $2" in
+  "image pull") cp "$TEST_REGISTRY/upstream" "$TEST_REGISTRY/local" ;;
+  "image inspect")
+    [ -f "$TEST_REGISTRY/local" ] || exit 1
+    printf '["rosettacommons/rosetta@%s"]\n' "$(cat "$TEST_REGISTRY/local")" ;;
+esac
+exit 0
+"#;
+
+fn registry() -> common::Sandbox {
+    let registry = common::Sandbox::new();
+    registry.shim.install_script("docker", DOCKER_SHIM);
+    registry.dir.child("registry").create_dir_all().unwrap();
+    registry
+}
+
+fn publish(registry: &common::Sandbox, digest: &str) {
+    registry
+        .dir
+        .child("registry/upstream")
+        .write_str(digest)
+        .unwrap();
+}
+
+fn update(registry: &common::Sandbox, engine: &str) -> assert_cmd::assert::Assert {
+    rc(registry, &["update", "rosetta", "-e", engine])
+}
+
+fn rc(registry: &common::Sandbox, args: &[&str]) -> assert_cmd::assert::Assert {
+    registry
+        .rc()
+        .args(args)
+        .env("TEST_REGISTRY", registry.dir.child("registry").path())
+        .assert()
+}
+
+fn pulls(registry: &common::Sandbox, tool: &str) -> usize {
+    registry.log().matches(&format!("{tool} pull")).count()
+}
+
+#[test]
+fn docker_update_reports_the_new_digest() {
+    let registry = registry();
+    publish(&registry, "sha256:aaaaaaaaaaaaaaaa");
+    update(&registry, "docker").success();
+
+    publish(&registry, "sha256:bbbbbbbbbbbbbbbb");
+    update(&registry, "docker")
+        .success()
+        .stdout(predicate::str::contains(
+            "sha256:aaaaaaaaaaaa -> sha256:bbbbbbbbbbbb",
+        ))
+        .stdout(predicate::str::contains("updated"));
+
+    update(&registry, "docker")
+        .success()
+        .stdout(predicate::str::contains("unchanged"));
+}
+
+#[test]
+fn sif_is_rebuilt_only_when_upstream_changed() {
+    let registry = registry();
+    let digest_file = registry
+        .dir
+        .child("cache/hpc/rosettacommons-rosetta:serial.sif.digest");
+
+    publish(&registry, "sha256:aaaaaaaaaaaaaaaa");
+    update(&registry, "singularity")
+        .success()
+        .stdout(predicate::str::contains("installed"));
+    digest_file.assert("sha256:aaaaaaaaaaaaaaaa");
+    assert_eq!(pulls(&registry, "singularity"), 1);
+
+    update(&registry, "singularity")
+        .success()
+        .stdout(predicate::str::contains("unchanged"));
+    assert_eq!(pulls(&registry, "singularity"), 1, "image was rebuilt");
+
+    publish(&registry, "sha256:bbbbbbbbbbbbbbbb");
+    update(&registry, "singularity")
+        .success()
+        .stdout(predicate::str::contains(
+            "sha256:aaaaaaaaaaaa -> sha256:bbbbbbbbbbbb",
+        ));
+    assert_eq!(pulls(&registry, "singularity"), 2);
+    digest_file.assert("sha256:bbbbbbbbbbbbbbbb");
+    // built from the digest that is recorded, not from the tag
+    let log = registry.log();
+    assert!(
+        log.contains("docker://rosettacommons/rosetta@sha256:bbbbbbbbbbbbbbbb"),
+        "{log}"
+    );
+    registry
+        .dir
+        .child("cache/hpc/rosettacommons-rosetta:serial.sif")
+        .assert(predicate::path::is_file());
+}
+
+#[test]
+fn sif_without_a_recorded_digest_is_updated() {
+    let registry = registry();
+    registry
+        .dir
+        .child("cache/hpc/rosettacommons-rosetta:serial.sif")
+        .write_str("pulled by an older rc")
+        .unwrap();
+
+    publish(&registry, "sha256:aaaaaaaaaaaaaaaa");
+    update(&registry, "singularity")
+        .success()
+        .stdout(predicate::str::contains("unknown -> sha256:aaaaaaaaaaaa"))
+        .stdout(predicate::str::contains("updated"));
+    assert_eq!(pulls(&registry, "singularity"), 1);
+}
+
+#[test]
+fn updating_every_app_skips_the_ones_not_installed() {
+    let registry = registry();
+    publish(&registry, "sha256:aaaaaaaaaaaaaaaa");
+    rc(&registry, &["update", "-e", "singularity"])
+        .success()
+        .stdout(predicate::str::contains("Updating").not());
+    assert_eq!(pulls(&registry, "singularity"), 0);
+
+    update(&registry, "singularity").success();
+    publish(&registry, "sha256:bbbbbbbbbbbbbbbb");
+    rc(&registry, &["update", "-e", "singularity"])
+        .success()
+        .stdout(predicate::str::contains("Updating rosetta for singularity"))
+        .stdout(predicate::str::contains("Updating rfdiffusion").not());
+    assert_eq!(pulls(&registry, "singularity"), 2);
+}